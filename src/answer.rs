@@ -0,0 +1,121 @@
+use std::fmt::{self, Display};
+
+// What a solver found, in the form it would be submitted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    Int(i64),
+    Text(String),
+    // A position, optionally followed by more values like a square's size
+    Coord(Vec<i64>),
+    // Rows of a picture that spells out the answer
+    Picture(Vec<String>),
+}
+
+impl Answer {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Answer::Int(_) => "integer",
+            Answer::Text(_) => "text",
+            Answer::Coord(_) => "coordinate",
+            Answer::Picture(_) => "picture",
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        match self {
+            Answer::Int(n) => n.to_string(),
+            Answer::Text(s) => json_string(s),
+            Answer::Coord(values) => json_array(values.iter().map(|v| v.to_string())),
+            Answer::Picture(rows) => json_array(rows.iter().map(|r| json_string(r))),
+        }
+    }
+}
+
+// Coordinates are shown as `x,y`, which is how the puzzle asks for them
+impl Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Answer::Int(n) => write!(f, "{}", n),
+            Answer::Text(s) => write!(f, "{}", s),
+            Answer::Coord(values) => {
+                let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+                write!(f, "{}", values.join(","))
+            }
+            Answer::Picture(rows) => write!(f, "{}", rows.join("\n")),
+        }
+    }
+}
+
+macro_rules! int_answer {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Answer {
+                fn from(n: $t) -> Self {
+                    Answer::Int(n as i64)
+                }
+            }
+        )*
+    };
+}
+
+int_answer!(u16, i32, u32, i64, u64, usize);
+
+impl From<String> for Answer {
+    fn from(s: String) -> Self {
+        Answer::Text(s)
+    }
+}
+
+impl<'a> From<&'a str> for Answer {
+    fn from(s: &'a str) -> Self {
+        Answer::Text(s.to_owned())
+    }
+}
+
+// A JSON string literal holding `s`
+pub fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn json_array<I: Iterator<Item = String>>(values: I) -> String {
+    format!("[{}]", values.collect::<Vec<_>>().join(","))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Answer::from(413usize).to_string(), "413");
+        assert_eq!(Answer::Coord(vec![237, 227, 14]).to_string(), "237,227,14");
+        let picture = Answer::Picture(vec!["#.#".to_owned(), ".#.".to_owned()]);
+        assert_eq!(picture.to_string(), "#.#\n.#.");
+    }
+
+    #[test]
+    fn test_json() {
+        assert_eq!(Answer::Int(-3).to_json(), "-3");
+        assert_eq!(Answer::Coord(vec![130, 104]).to_json(), "[130,104]");
+        assert_eq!(Answer::from("say \"hi\"\n").to_json(), r#""say \"hi\"\n""#);
+        assert_eq!(
+            Answer::Picture(vec!["#.".to_owned()]).to_json(),
+            r##"["#."]"##
+        );
+        assert_eq!(json_string("\u{1}"), r#""\u0001""#);
+    }
+}
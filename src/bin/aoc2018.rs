@@ -0,0 +1,444 @@
+use advent_of_code_2018::answer::{json_string, Answer};
+use advent_of_code_2018::render::{self, Frames};
+use advent_of_code_2018::runner::{self, Solution, Timing};
+use std::{
+    env, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+const USAGE: &str = "Usage:
+    aoc2018 run --day <day> [--part <1|2>] [--input <path|->] [--format <text|json>]
+    aoc2018 run --all [--input-dir <dir>] [--format <text|json>]
+    aoc2018 time (--day <day> [--part <1|2>] [--input <path|->] | --all [--input-dir <dir>])
+                 [--repeat <n>] [--output <path>]
+    aoc2018 render --day <6|10|13|15|17> [--input <path|->] --output <dir>
+                   [--format <png|ppm>] [--scale <n>] [--every <n>]
+    aoc2018 list
+
+Options:
+    -d, --day <day>          Day to run
+    -p, --part <part>        Only run the given part
+    -i, --input <path>       Puzzle input file, `-` or no path reads stdin
+    -a, --all                Run every day
+        --input-dir <dir>    Directory holding day<N>.txt inputs [default: input/2018]
+    -f, --format <format>    Print answers as `text` or a `json` array [default: text],
+                             or save frames as `png` or `ppm` images [default: png]
+    -r, --repeat <n>         Times to run each step when timing [default: 5]
+    -o, --output <path>      Also write the timings to a CSV file, or the directory
+                             frames are saved in
+    -s, --scale <n>          Draw each cell as an n by n square [default: 1]
+    -e, --every <n>          Only save every nth frame [default: 1]";
+
+const DEFAULT_INPUT_DIR: &str = "input/2018";
+const DEFAULT_REPEATS: u32 = 5;
+
+enum Command {
+    Run {
+        days: Days,
+        part: Option<u8>,
+        format: Format,
+    },
+    Time {
+        days: Days,
+        part: Option<u8>,
+        repeats: u32,
+        output: Option<PathBuf>,
+    },
+    Render {
+        day: u8,
+        input: Option<PathBuf>,
+        output: PathBuf,
+        format: render::Format,
+        scale: usize,
+        every: usize,
+    },
+    List,
+}
+
+// Which days to run and where their input comes from
+enum Days {
+    One { day: u8, input: Option<PathBuf> },
+    All { input_dir: PathBuf },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = match parse_args(&args) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(command) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut args = args.iter();
+    let command = match args.next().map(String::as_str) {
+        Some(c @ "run") | Some(c @ "time") | Some(c @ "render") => c,
+        Some("list") => return Ok(Command::List),
+        Some(other) => return Err(format!("unknown command `{}`", other)),
+        None => return Err("missing command".to_owned()),
+    };
+
+    let (mut day, mut part, mut input, mut all, mut input_dir) = (None, None, None, false, None);
+    let (mut format, mut repeats, mut output, mut scale, mut every) =
+        (None, None, None, None, None);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for `{}`", arg))
+        };
+        match arg.as_str() {
+            "-d" | "--day" => day = Some(parse_number(value()?, "day")?),
+            "-p" | "--part" => part = Some(parse_number(value()?, "part")?),
+            "-i" | "--input" => input = Some(PathBuf::from(value()?)),
+            "-a" | "--all" => all = true,
+            "--input-dir" => input_dir = Some(PathBuf::from(value()?)),
+            "-f" | "--format" => format = Some(value()?.as_str()),
+            "-r" | "--repeat" => repeats = Some(parse_number(value()?, "repeat count")?),
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "-s" | "--scale" => scale = Some(parse_number(value()?, "scale")?),
+            "-e" | "--every" => every = Some(parse_number(value()?, "frame step")?),
+            other => return Err(format!("unexpected argument `{}`", other)),
+        }
+    }
+
+    if command == "render" {
+        if all || input_dir.is_some() || part.is_some() || repeats.is_some() {
+            return Err("`render` draws a single day, see `--day`".to_owned());
+        }
+        return Ok(Command::Render {
+            day: day.ok_or("expected `--day <day>`")?,
+            input,
+            output: output.ok_or("expected `--output <dir>` to save frames in")?,
+            format: match format.unwrap_or("png") {
+                "png" => render::Format::Png,
+                "ppm" => render::Format::Ppm,
+                other => return Err(format!("unknown image format `{}`", other)),
+            },
+            scale: positive(scale, "--scale")?,
+            every: positive(every, "--every")?,
+        });
+    }
+    if scale.is_some() || every.is_some() {
+        return Err("`--scale` and `--every` are only used with `render`".to_owned());
+    }
+
+    let days = match (all, day) {
+        (true, Some(_)) => return Err("`--all` cannot be combined with `--day`".to_owned()),
+        (true, None) if part.is_some() || input.is_some() => {
+            return Err("`--all` runs both parts of every day from `--input-dir`".to_owned())
+        }
+        (true, None) => Days::All {
+            input_dir: input_dir.unwrap_or_else(|| PathBuf::from(DEFAULT_INPUT_DIR)),
+        },
+        (false, Some(day)) => {
+            if input_dir.is_some() {
+                return Err("`--input-dir` is only used with `--all`".to_owned());
+            }
+            Days::One { day, input }
+        }
+        (false, None) => return Err("expected `--day <day>` or `--all`".to_owned()),
+    };
+    match part {
+        None | Some(1) | Some(2) => (),
+        Some(p) => return Err(format!("part must be 1 or 2, got {}", p)),
+    }
+
+    if command == "time" {
+        if format.is_some() {
+            return Err("`--format` is only used with `run`".to_owned());
+        }
+        match repeats.unwrap_or(DEFAULT_REPEATS) {
+            0 => Err("`--repeat` must be at least 1".to_owned()),
+            repeats => Ok(Command::Time {
+                days,
+                part,
+                repeats,
+                output,
+            }),
+        }
+    } else {
+        if repeats.is_some() || output.is_some() {
+            return Err("`--repeat` and `--output` are only used with `time`".to_owned());
+        }
+        Ok(Command::Run {
+            days,
+            part,
+            format: match format.unwrap_or("text") {
+                "text" => Format::Text,
+                "json" => Format::Json,
+                other => return Err(format!("unknown format `{}`", other)),
+            },
+        })
+    }
+}
+
+fn positive(value: Option<usize>, name: &str) -> Result<usize, String> {
+    match value.unwrap_or(1) {
+        0 => Err(format!("`{}` must be at least 1", name)),
+        n => Ok(n),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {} `{}`", name, value))
+}
+
+fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Run { days, part, format } => {
+            let mut output = Output::new(format);
+            let failed = each_day(&days, part, |solution, input| {
+                let answers =
+                    input.and_then(|input| solution.solve(&input, part).map_err(|e| e.to_string()));
+                match answers {
+                    Ok(answers) => {
+                        for (p, answer) in answers {
+                            output.answer(solution.day, p, &answer);
+                        }
+                        true
+                    }
+                    Err(e) => {
+                        output.error(solution.day, &e);
+                        false
+                    }
+                }
+            })?;
+            output.finish();
+            check_failed(failed)?;
+        }
+        Command::Time {
+            days,
+            part,
+            repeats,
+            output,
+        } => {
+            println!(
+                "Day  {:<10} {:>5} {:>12} {:>12} {:>12}",
+                "Step", "Runs", "Min", "Median", "Mean"
+            );
+            let mut csv = vec!["day,step,runs,min_ms,median_ms,mean_ms".to_owned()];
+            let failed = each_day(&days, part, |solution, input| {
+                let timings = input.and_then(|input| {
+                    solution
+                        .time(&input, part, repeats)
+                        .map_err(|e| e.to_string())
+                });
+                match timings {
+                    Ok(timings) => {
+                        for t in timings {
+                            print_timing(solution.day, &t);
+                            csv.push(timing_csv(solution.day, &t));
+                        }
+                        true
+                    }
+                    Err(e) => {
+                        eprintln!("error: {}", e);
+                        false
+                    }
+                }
+            })?;
+            if let Some(path) = output {
+                fs::write(&path, csv.join("\n") + "\n")
+                    .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+            }
+            check_failed(failed)?;
+        }
+        Command::Render {
+            day,
+            input,
+            output,
+            format,
+            scale,
+            every,
+        } => {
+            let input = match input {
+                Some(ref path) if path != Path::new("-") => read_file(path)?,
+                _ => read_stdin()?,
+            };
+            let mut frames = Frames::to_dir(&output, format)
+                .map_err(|e| e.to_string())?
+                .scale(scale)
+                .every(every);
+            runner::render(day, &input, &mut frames).map_err(|e| e.to_string())?;
+            println!(
+                "Day {:>2} - {} frame(s) saved in {}",
+                day,
+                frames.written().len(),
+                output.display()
+            );
+        }
+        Command::List => {
+            for solution in runner::SOLUTIONS {
+                let parts = if solution.has_part(2) { "1, 2" } else { "1" };
+                println!("Day {:>2} - parts {}", solution.day, parts);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Calls `f` with each day's solution and input, or the error from reading
+// it. `f` returns whether the day succeeded, and the number of days that
+// didn't is returned. Keeps going past a bad day so that it can't hide the
+// others' results
+fn each_day<F>(days: &Days, part: Option<u8>, mut f: F) -> Result<usize, String>
+where
+    F: FnMut(&Solution, Result<String, String>) -> bool,
+{
+    let mut failed = 0;
+    match days {
+        Days::One { day, input } => {
+            let solution =
+                runner::find(*day).ok_or_else(|| format!("no solution for day {}", day))?;
+            if let Some(p) = part {
+                if !solution.has_part(p) {
+                    return Err(format!("day {} has no part {}", day, p));
+                }
+            }
+            let input = match input {
+                Some(ref path) if path != Path::new("-") => read_file(path),
+                _ => read_stdin(),
+            };
+            if !f(solution, input) {
+                failed += 1;
+            }
+        }
+        Days::All { input_dir } => {
+            for solution in runner::SOLUTIONS {
+                let path = input_dir.join(format!("day{}.txt", solution.day));
+                if !f(solution, read_file(&path)) {
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    Ok(failed)
+}
+
+fn check_failed(failed: usize) -> Result<(), String> {
+    match failed {
+        0 => Ok(()),
+        n => Err(format!("{} day(s) failed", n)),
+    }
+}
+
+fn print_timing(day: u8, timing: &Timing) {
+    println!(
+        "{:>3}  {:<10} {:>5} {:>12} {:>12} {:>12}",
+        day,
+        timing.step.to_string(),
+        timing.samples.len(),
+        format_duration(timing.min()),
+        format_duration(timing.median()),
+        format_duration(timing.mean())
+    );
+}
+
+fn timing_csv(day: u8, timing: &Timing) -> String {
+    format!(
+        "{},{},{},{:.3},{:.3},{:.3}",
+        day,
+        timing.step,
+        timing.samples.len(),
+        millis(timing.min()),
+        millis(timing.median()),
+        millis(timing.mean())
+    )
+}
+
+fn format_duration(d: Duration) -> String {
+    format!("{:.3}ms", millis(d))
+}
+
+fn millis(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+// Text is printed as soon as it's known, JSON is gathered up and
+// printed as a single array by `finish`
+struct Output {
+    format: Format,
+    json: Vec<String>,
+}
+
+impl Output {
+    fn new(format: Format) -> Self {
+        Output {
+            format,
+            json: Vec::new(),
+        }
+    }
+
+    fn answer(&mut self, day: u8, part: u8, answer: &Answer) {
+        match self.format {
+            Format::Text => match answer {
+                Answer::Picture(rows) => {
+                    println!("Day {:>2} - Part {}:", day, part);
+                    for row in rows {
+                        println!("    {}", row);
+                    }
+                }
+                _ => println!("Day {:>2} - Part {}: {}", day, part, answer),
+            },
+            Format::Json => self.json.push(format!(
+                r#"{{"day":{},"part":{},"type":"{}","answer":{}}}"#,
+                day,
+                part,
+                answer.kind(),
+                answer.to_json()
+            )),
+        }
+    }
+
+    fn error(&mut self, day: u8, error: &str) {
+        match self.format {
+            Format::Text => eprintln!("error: {}", error),
+            Format::Json => self.json.push(format!(
+                r#"{{"day":{},"error":{}}}"#,
+                day,
+                json_string(error)
+            )),
+        }
+    }
+
+    fn finish(self) {
+        match (self.format, self.json.is_empty()) {
+            (Format::Json, true) => println!("[]"),
+            (Format::Json, false) => println!("[\n  {}\n]", self.json.join(",\n  ")),
+            (Format::Text, _) => (),
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))
+}
+
+fn read_stdin() -> Result<String, String> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| format!("could not read stdin: {}", e))?;
+    Ok(input)
+}
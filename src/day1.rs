@@ -1,65 +1,324 @@
-use std::{collections::HashSet, i32};
+use crate::error::{Line, ParseError, ReadError};
+use crate::input;
+use std::io::{self, BufRead, Write};
 
-#[aoc_generator(day1, part2)]
-pub fn input_gen(input: &str) -> Vec<i32> {
-    input.lines().map(|l| l.parse::<i32>().unwrap()).collect()
+const DAY: u8 = 1;
+
+#[aoc_generator(day1)]
+pub fn input_gen(input: &str) -> Result<Vec<i32>, ParseError> {
+    let input = &input::normalize(input);
+    read_input(input.as_bytes()).map_err(ReadError::into_parse)
+}
+
+// The changes read a line at a time. Part 2 goes round them over and
+// over so it needs them all, but not the text they came from
+pub fn read_input<R: BufRead>(reader: R) -> Result<Vec<i32>, ReadError> {
+    changes(reader).collect()
 }
 
 #[aoc(day1, part1)]
-pub fn solve_part1(input: &str) -> i32 {
-    input.lines().map(|l| l.parse::<i32>().unwrap()).sum()
+pub fn solve_part1(input: &[i32]) -> i32 {
+    input.iter().sum()
+}
+
+// Part 1 without keeping any of the changes
+pub fn solve_part1_from<R: BufRead>(reader: R) -> Result<i32, ReadError> {
+    changes(reader).sum()
 }
 
+fn changes<R: BufRead>(reader: R) -> impl Iterator<Item = Result<i32, ReadError>> {
+    input::lines(reader).enumerate().map(|(i, text)| {
+        let text = text?;
+        Ok(Line::new(DAY, i + 1, &text).parse(&text)?)
+    })
+}
+
+// Each time round the changes, every frequency from the first time round
+// is shifted by the drift, the sum of all the changes. So a frequency can
+// only come back as one that's a whole number of drifts away from it, the
+// nearest one in the direction of the drift, and that's found by sorting
+// the first time round rather than going round until it turns up.
+// `None` if no frequency is ever reached twice
 #[aoc(day1, part2)]
-pub fn solve_part2(input: &[i32]) -> i32 {
-    let mut set = HashSet::new();
-    let mut freq = 0;
-    set.insert(freq);
-    for change in input.iter().cycle() {
-        freq += change;
-        if set.contains(&freq) {
-            break;
+pub fn solve_part2(input: &[i32]) -> Option<i32> {
+    first_repeat(input).map(|(_, freq)| freq as i32)
+}
+
+// The step a frequency is first reached again, counting the start as step
+// 0, and what it is
+fn first_repeat(input: &[i32]) -> Option<(u128, i64)> {
+    let drift: i64 = input.iter().map(|&c| i64::from(c)).sum();
+    let mut firsts = Vec::with_capacity(input.len());
+    let mut freq = 0i64;
+    for (step, &change) in input.iter().enumerate() {
+        firsts.push(First::new(freq, step, drift));
+        freq += i64::from(change);
+    }
+    firsts.sort_by_key(|f| (f.class, f.rank, f.step));
+
+    // The step a frequency is reached again, and what it is. With no
+    // drift the start comes back at the end of the first time round
+    let mut first_repeat = match drift {
+        0 if !input.is_empty() => Some((input.len() as u128, 0)),
+        _ => None,
+    };
+    for pair in firsts.windows(2) {
+        let (from, to) = (&pair[0], &pair[1]);
+        if from.class != to.class {
+            continue;
+        }
+        let rounds = ((to.rank - from.rank) / drift.abs().max(1)) as u128;
+        let step = match rounds {
+            0 => to.step as u128,
+            _ => rounds * input.len() as u128 + from.step as u128,
+        };
+        if first_repeat.is_none_or(|(s, _)| step < s) {
+            first_repeat = Some((step, to.freq));
+        }
+    }
+
+    first_repeat
+}
+
+// The device's frequency after every change, going round the changes
+// `rounds` times from 0
+pub struct Trace {
+    pub readings: Vec<Reading>,
+    // Where the first repeat is, even if it's after the traced rounds
+    pub first_repeat: Option<Reading>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reading {
+    // Counted from 0, like `position` in the list of changes
+    pub round: u64,
+    pub position: usize,
+    pub change: i32,
+    pub frequency: i64,
+}
+
+impl Trace {
+    pub fn new(changes: &[i32], rounds: usize) -> Self {
+        let mut readings = Vec::with_capacity(changes.len() * rounds);
+        let mut frequency = 0;
+        for round in 0..rounds {
+            for (position, &change) in changes.iter().enumerate() {
+                frequency += i64::from(change);
+                readings.push(Reading {
+                    round: round as u64,
+                    position,
+                    change,
+                    frequency,
+                });
+            }
+        }
+
+        let first_repeat = first_repeat(changes).map(|(step, frequency)| {
+            let (before, n) = (step - 1, changes.len() as u128);
+            let position = (before % n) as usize;
+            Reading {
+                round: (before / n) as u64,
+                position,
+                change: changes[position],
+                frequency,
+            }
+        });
+
+        Trace {
+            readings,
+            first_repeat,
+        }
+    }
+
+    // The lowest and highest frequencies in the trace, including the 0 it starts at
+    pub fn min(&self) -> i64 {
+        self.readings
+            .iter()
+            .map(|r| r.frequency)
+            .min()
+            .unwrap_or(0)
+            .min(0)
+    }
+
+    pub fn max(&self) -> i64 {
+        self.readings
+            .iter()
+            .map(|r| r.frequency)
+            .max()
+            .unwrap_or(0)
+            .max(0)
+    }
+
+    pub fn write_csv<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "round,position,change,frequency")?;
+        for r in &self.readings {
+            writeln!(w, "{},{},{},{}", r.round, r.position, r.change, r.frequency)?;
+        }
+        Ok(())
+    }
+}
+
+// A frequency reached the first time round the changes
+struct First {
+    freq: i64,
+    step: usize,
+    // Frequencies that can become each other after some number of drifts
+    class: i64,
+    // How far along the drift it is
+    rank: i64,
+}
+
+impl First {
+    fn new(freq: i64, step: usize, drift: i64) -> Self {
+        First {
+            freq,
+            step,
+            class: if drift == 0 {
+                freq
+            } else {
+                freq.rem_euclid(drift.abs())
+            },
+            rank: freq * drift.signum(),
         }
-        set.insert(freq);
     }
-    return freq;
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{input_gen, solve_part1, solve_part2};
+    use super::{input_gen, read_input, solve_part1, solve_part1_from, solve_part2, Trace};
+    use crate::error::ReadError;
+    use crate::generators;
+    use std::io::BufReader;
 
     #[test]
     fn should_return_3() {
-        let input = "0\n-2\n5";
-        assert_eq!(3, solve_part1(input));
+        let input = input_gen("0\n-2\n5").unwrap();
+        assert_eq!(3, solve_part1(&input));
     }
 
     #[test]
     fn should_return_55() {
         let data = vec!["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"];
-        let input = data.join("\n");
+        let input = input_gen(&data.join("\n")).unwrap();
         assert_eq!((10 * 11) / 2, solve_part1(&input));
     }
 
+    #[test]
+    fn should_report_bad_line() {
+        let err = input_gen("+1\n-2\n+x").unwrap_err();
+        assert_eq!((err.day, err.line, err.column), (1, 3, 1));
+        assert_eq!(err.text, "+x");
+    }
+
+    #[test]
+    fn should_read_a_stream() {
+        let data = "\u{feff}+1\r\n-2\r\n+3\r\n\r\n";
+        let reader = BufReader::with_capacity(4, data.as_bytes());
+        assert_eq!(read_input(reader).unwrap(), vec![1, -2, 3]);
+        assert_eq!(solve_part1_from(data.as_bytes()).unwrap(), 2);
+
+        match solve_part1_from("+1\n\n+2\n".as_bytes()) {
+            Err(ReadError::Parse(err)) => assert_eq!(err.line, 2),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+        let invalid: &[u8] = b"+1\n+\xff\n";
+        assert!(matches!(solve_part1_from(invalid), Err(ReadError::Io(_))));
+    }
+
     // part 2 tests
     #[test]
     fn should_return_0() {
-        let data = vec!["1", "-1"];
+        let data = ["1", "-1"];
         let data = data.join("\n");
-        let input = input_gen(&data);
-        assert_eq!(0, solve_part2(&input));
+        let input = input_gen(&data).unwrap();
+        assert_eq!(Some(0), solve_part2(&input));
     }
 
     #[test]
     fn should_return_10() {
         let input = vec![3, 3, 4, -2, -4];
-        assert_eq!(10, solve_part2(&input));
+        assert_eq!(Some(10), solve_part2(&input));
     }
 
     #[test]
     fn should_return_14() {
         let input = vec![7, 7, -2, -7, -4];
-        assert_eq!(14, solve_part2(&input));
+        assert_eq!(Some(14), solve_part2(&input));
+    }
+
+    #[test]
+    fn should_never_repeat() {
+        assert_eq!(None, solve_part2(&[1]));
+        assert_eq!(None, solve_part2(&[3, 3]));
+        assert_eq!(None, solve_part2(&[]));
+        assert_eq!(Some(1), solve_part2(&[1, 1, -1]));
+    }
+
+    // Keeps every frequency in order and searches all of them. A repeat
+    // comes within as many times round as the spread of the first time
+    // round is wide in drifts, if it comes at all
+    fn first_repeat(changes: &[i32]) -> Option<i32> {
+        let mut seen = vec![0];
+        for &change in changes.iter().take(changes.len().saturating_sub(1)) {
+            seen.push(seen.last().unwrap() + change);
+        }
+        let spread = seen.iter().max()? - seen.iter().min()?;
+        let drift: i32 = changes.iter().sum();
+        let rounds = spread as usize / drift.unsigned_abs().max(1) as usize + 2;
+
+        seen.truncate(1);
+        for change in changes.iter().cycle().take(rounds * changes.len()) {
+            let freq = seen.last().unwrap() + change;
+            if seen.contains(&freq) {
+                return Some(freq);
+            }
+            seen.push(freq);
+        }
+        None
+    }
+
+    #[test]
+    fn should_match_reference() {
+        generators::check(200, |rng| {
+            let input = input_gen(&generators::day1(rng)).unwrap();
+            assert_eq!(solve_part2(&input), first_repeat(&input));
+        });
+    }
+
+    #[test]
+    fn should_trace_frequencies() {
+        let trace = Trace::new(&[3, 3, 4, -2, -4], 2);
+        let frequencies: Vec<_> = trace.readings.iter().map(|r| r.frequency).collect();
+        assert_eq!(frequencies, vec![3, 6, 10, 8, 4, 7, 10, 14, 12, 8]);
+        assert_eq!((trace.min(), trace.max()), (0, 14));
+
+        let repeat = trace.first_repeat.unwrap();
+        assert_eq!(
+            (repeat.round, repeat.position, repeat.frequency),
+            (1, 1, 10)
+        );
+        assert_eq!(trace.readings[6], repeat);
+
+        let mut csv = Vec::new();
+        trace.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 11);
+        assert!(csv.starts_with("round,position,change,frequency\n0,0,3,3\n"));
+        assert!(csv.ends_with("1,4,-4,8\n"));
+
+        // Far past the traced rounds
+        let trace = Trace::new(&[1_000_000, -999_999], 1);
+        let repeat = trace.first_repeat.unwrap();
+        assert_eq!((repeat.round, repeat.position), (999_999, 1));
+        assert!(Trace::new(&[1], 3).first_repeat.is_none());
+    }
+
+    #[test]
+    fn should_find_far_repeats() {
+        // A million times round before 1000000 is reached again
+        assert_eq!(Some(1_000_000), solve_part2(&[1_000_000, -999_999]));
+        assert_eq!(Some(-2), solve_part2(&[-7, 5, 1]));
+        let input = [7, -3, 12, -20, 9, -1];
+        assert_eq!(solve_part2(&input), first_repeat(&input));
     }
 }
@@ -1,14 +1,46 @@
+use crate::answer::Answer;
+use crate::error::{self, Line, ParseError};
+use crate::grid::{Grid, Point};
+use crate::input;
+use crate::render::{Frames, Image, RenderError, Rgb};
 use regex::Regex;
-use std::{cmp, i32, num::ParseIntError, str::FromStr};
+use std::{cmp, str::FromStr};
 
+const DAY: u8 = 10;
+
+// The message is found once the lights start spreading out again, which
+// they only do across and down if their velocities differ both ways
 #[aoc_generator(day10)]
-fn input_gen(input: &str) -> Vec<Light> {
-    input.lines().map(|l| l.parse().unwrap()).collect()
+pub fn input_gen(input: &str) -> Result<Vec<Light>, ParseError> {
+    let input = &input::normalize(input);
+    let lights: Vec<Light> = error::lines(DAY, input)
+        .map(|l| l.parse_as())
+        .collect::<Result<_, _>>()?;
+    let spread = |v: fn(&Light) -> i32| lights.iter().any(|l| v(l) != v(&lights[0]));
+    if !spread(|l| l.vx) || !spread(|l| l.vy) {
+        return Err(ParseError::missing(
+            DAY,
+            lights.len() + 1,
+            "lights moving apart across and down",
+        ));
+    }
+    Ok(lights)
 }
 
-// Part 2's answer is the number of seconds
 #[aoc(day10, part1)]
-fn solve_part1(input: &[Light]) -> u32 {
+pub fn solve_part1(input: &[Light]) -> Answer {
+    let (lights, _) = find_message(input);
+    Answer::Picture(draw(&lights))
+}
+
+#[aoc(day10, part2)]
+pub fn solve_part2(input: &[Light]) -> u32 {
+    find_message(input).1
+}
+
+// Moves the lights until they spell out the message, returning them
+// along with the number of seconds it took
+fn find_message(input: &[Light]) -> (Vec<Light>, u32) {
     let mut lights = input.to_vec();
     let mut rect = bounding_rect(&lights, Rectangle::new());
     let (mut width, mut height) = (rect.width(), rect.height());
@@ -36,27 +68,61 @@ fn solve_part1(input: &[Light]) -> u32 {
         light.undo();
     }
 
-    draw(&lights, &rect);
-    println!("area: {} seconds: {}", width * height, seconds);
+    (lights, seconds)
+}
 
-    // Solver's need a return value for aoc-runner to work
-    0
+// The last few seconds of the lights coming together, ending on the
+// message. Every frame shows the area the lights start in
+pub fn render(input: &[Light], frames: &mut Frames) -> Result<(), RenderError> {
+    const SECONDS: i32 = 10;
+    let (mut lights, seconds) = find_message(input);
+    let before = cmp::min(seconds as i32, SECONDS);
+    for light in lights.iter_mut() {
+        light.offset(light.vx * before, light.vy * before);
+    }
+
+    let rect = bounding_rect(&lights, Rectangle::new());
+    let (width, height) = (rect.width() as usize + 1, rect.height() as usize + 1);
+    for second in 0..=before {
+        let mut sky = Grid::new(width, height, false);
+        for l in &lights {
+            let (x, y) = (l.x - rect.x1, l.y - rect.y1);
+            if (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+                sky[Point::new(x as usize, y as usize)] = true;
+            }
+        }
+        frames.push(Image::from_grid(&sky, |&lit| {
+            if lit {
+                Rgb(255, 230, 120)
+            } else {
+                Rgb(10, 10, 40)
+            }
+        }))?;
+
+        if second < before {
+            for light in lights.iter_mut() {
+                light.update();
+            }
+        }
+    }
+
+    Ok(())
 }
 
-fn draw(lights: &[Light], rect: &Rectangle) {
-    let mut lines = vec![vec!["."; rect.width() as usize]; rect.height() as usize];
+fn draw(lights: &[Light]) -> Vec<String> {
+    let rect = bounding_rect(lights, Rectangle::new());
+    let (width, height) = (rect.width() as usize + 1, rect.height() as usize + 1);
+    let mut sky = Grid::new(width, height, '.');
     for mut p in lights.iter().cloned() {
         p.offset(rect.x1, rect.y1);
-        lines[p.y as usize][p.x as usize] = "#";
+        sky[Point::new(p.x as usize, p.y as usize)] = '#';
     }
 
-    for line in lines.iter().map(|l| l.join(" ")) {
-        println!("{}", line);
-    }
+    sky.rows().map(|row| row.iter().collect()).collect()
 }
 
 fn bounding_rect(lights: &[Light], rect: Rectangle) -> Rectangle {
-    let (mut min_x, mut max_x, mut min_y, mut max_y) = (i32::MAX, 0, i32::MAX, 0);
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (i32::MAX, i32::MIN, i32::MAX, i32::MIN);
 
     for l in lights {
         min_x = cmp::min(min_x, l.x);
@@ -93,10 +159,6 @@ impl Rectangle {
         self
     }
 
-    fn area(&self) -> i32 {
-        self.width() * self.height()
-    }
-
     fn width(&self) -> i32 {
         i32::abs(self.x1 - self.x2)
     }
@@ -106,8 +168,8 @@ impl Rectangle {
     }
 }
 
-#[derive(Clone)]
-struct Light {
+#[derive(Clone, Debug)]
+pub struct Light {
     x: i32,
     y: i32,
     vx: i32,
@@ -115,7 +177,7 @@ struct Light {
 }
 
 impl FromStr for Light {
-    type Err = ParseIntError;
+    type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         lazy_static! {
             static ref regexp: Regex = Regex::new(
@@ -125,12 +187,15 @@ impl FromStr for Light {
             )
             .unwrap();
         }
-        let caps = regexp.captures(s).unwrap();
+        let line = Line::new(DAY, 1, s);
+        let caps = regexp
+            .captures(s)
+            .ok_or_else(|| line.malformed("a light like `position=< 9,  1> velocity=< 0,  2>`"))?;
         Ok(Light::new(
-            caps["x"].parse()?,
-            caps["y"].parse()?,
-            caps["vx"].parse()?,
-            caps["vy"].parse()?,
+            line.parse(caps.name("x").unwrap().as_str())?,
+            line.parse(caps.name("y").unwrap().as_str())?,
+            line.parse(caps.name("vx").unwrap().as_str())?,
+            line.parse(caps.name("vy").unwrap().as_str())?,
         ))
     }
 }
@@ -159,11 +224,80 @@ impl Light {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::ErrorKind;
+    use crate::generators;
+    use std::collections::HashSet;
 
     #[test]
     fn test_input() {
         let input = include_str!("../input/tests/d10.rs");
-        let lights = input_gen(input);
+        let lights = input_gen(input).unwrap();
         assert_eq!(lights.len(), 31);
     }
+
+    #[test]
+    fn test_message() {
+        let lights = input_gen(include_str!("../input/tests/d10.rs")).unwrap();
+        let message = solve_part1(&lights).to_string();
+        assert_eq!(message.lines().next(), Some("#...#..###"));
+        assert_eq!(message.lines().count(), 8);
+        assert_eq!(solve_part2(&lights), 3);
+    }
+
+    // The bounding box used to start its maximums at 0, so a message left
+    // of or above the origin was never seen to shrink
+    #[test]
+    fn test_negative_message() {
+        let lights = input_gen(include_str!("../input/tests/d10.rs")).unwrap();
+        let moved: Vec<_> = lights
+            .iter()
+            .map(|l| Light::new(l.x - 50, l.y - 50, l.vx, l.vy))
+            .collect();
+        assert_eq!(solve_part1(&moved), solve_part1(&lights));
+        assert_eq!(solve_part2(&moved), 3);
+    }
+
+    #[test]
+    fn test_bad_light() {
+        let err = input_gen("position=< 9,  1> velocity=< 0,  2>\nposition=< 9,  1>").unwrap_err();
+        assert_eq!((err.day, err.line), (10, 2));
+    }
+
+    #[test]
+    fn test_never_spreading() {
+        let err = input_gen("").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::Missing("lights moving apart across and down")
+        );
+        let input = "position=< 0, 0> velocity=< 1, 2>\nposition=< 5, 0> velocity=< 1, -1>\n";
+        assert_eq!(input_gen(input).unwrap_err().line, 3);
+        let input = "position=< 0, 0> velocity=< 1, 2>\nposition=< 5, 0> velocity=< 2, -1>\n";
+        assert_eq!(input_gen(input).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_random_messages() {
+        generators::check(100, |rng| {
+            let (input, seconds) = generators::day10(rng);
+            let lights = input_gen(&input).unwrap();
+            let seconds = seconds as i32;
+            let lit: HashSet<(i32, i32)> = lights
+                .iter()
+                .map(|l| (l.x + l.vx * seconds, l.y + l.vy * seconds))
+                .collect();
+            let (min_x, max_x) = (lit.iter().map(|l| l.0).min(), lit.iter().map(|l| l.0).max());
+            let (min_y, max_y) = (lit.iter().map(|l| l.1).min(), lit.iter().map(|l| l.1).max());
+            let picture = (min_y.unwrap()..=max_y.unwrap())
+                .map(|y| {
+                    (min_x.unwrap()..=max_x.unwrap())
+                        .map(|x| if lit.contains(&(x, y)) { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+
+            assert_eq!(solve_part1(&lights), Answer::Picture(picture));
+            assert_eq!(solve_part2(&lights), seconds as u32);
+        });
+    }
 }
@@ -1,72 +1,71 @@
-use std::fmt::{self, Display};
-
-const GRID_SERIAL_NUMBER: usize = 1133;
+use crate::answer::Answer;
+use crate::error::{Line, ParseError};
+use crate::grid::{Grid, Point};
+use crate::input;
+
+const DAY: u8 = 11;
+const GRID_SIZE: usize = 300;
+
+// The grid serial number
+#[aoc_generator(day11)]
+pub fn input_gen(input: &str) -> Result<i32, ParseError> {
+    let input = &input::normalize(input);
+    let line = Line::new(DAY, 1, input);
+    if input.trim().is_empty() {
+        return Err(ParseError::missing(DAY, 1, "the grid serial number"));
+    }
+    line.parse(input.trim())
+}
 
 #[aoc(day11, part1)]
-fn solve_part1(_: &str) -> Coords {
-    let mut grid = vec![vec![0; 300]; 300];
-    populate_fuel_cells(&mut grid);
-    let (x, y, _, total) = square_total(&grid, 3);
-
-    println!("{}", total);
-    print_square(&grid, 3, x - 1, y - 1);
+pub fn solve_part1(serial: &i32) -> Answer {
+    let sums = summed_area(&fuel_cells(*serial));
+    let (x, y, _, _) = square_total(&sums, 3);
 
-
-    Coords {
-        x: x as u32,
-        y: y as u32,
-        size: 3,
-    }
+    Answer::Coord(vec![x as i64, y as i64])
 }
 
 #[aoc(day11, part2)]
-fn solve_part2(_: &str) -> Coords {
-    let mut grid = vec![vec![0; 300]; 300];
-    let mut best = (0, 0, 0, 0);
-
-    populate_fuel_cells(&mut grid);
+pub fn solve_part2(serial: &i32) -> Answer {
+    let sums = summed_area(&fuel_cells(*serial));
+    let mut best = (0, 0, 0, i32::MIN);
 
-    // Brute force - check best totals for 4 <= square_size  < 300
-    for i in 4..300 {
-        let res = square_total(&grid, i);
+    // Check best totals for every square size
+    for i in 1..=GRID_SIZE {
+        let res = square_total(&sums, i);
         if res.3 > best.3 {
             best = res;
         }
     }
-    let (x, y, size, total) = best;
+    let (x, y, size, _) = best;
 
-
-    println!("Total: {}", total);
-    Coords {
-        x: x as u32,
-        y: y as u32,
-        size: size as u32,
-    }
+    Answer::Coord(vec![x as i64, y as i64, size as i64])
 }
 
-fn print_square(grid: &Vec<Vec<i32>>, square_size: usize, x: usize, y: usize) {
-    let mut square_total = 0;
-    for s_y in y..y + square_size {
-        for s_x in x..x + square_size {
-            print!("{} ", grid[s_y][s_x]);
+// One row and column bigger than the grid, each cell holding the total of
+// everything above and to the left of it
+fn summed_area(grid: &Grid<i32>) -> Grid<i32> {
+    let mut sums = Grid::new(GRID_SIZE + 1, GRID_SIZE + 1, 0);
+    for y in 0..GRID_SIZE {
+        for x in 0..GRID_SIZE {
+            sums[Point::new(x + 1, y + 1)] =
+                grid[Point::new(x, y)] + sums[Point::new(x + 1, y)] + sums[Point::new(x, y + 1)]
+                    - sums[Point::new(x, y)];
         }
-
-        println!("");
     }
+    sums
 }
 
-fn square_total(grid: &Vec<Vec<i32>>, square_size: usize) -> (usize, usize, usize, i32) {
-    let mut best = 0;
+fn square_total(sums: &Grid<i32>, square_size: usize) -> (usize, usize, usize, i32) {
+    let mut best = i32::MIN;
     let mut coords = (1, 1);
 
-    for y in 0..300 - square_size {
-        for x in 0..300 - square_size {
-            let mut square_total = 0;
-            for s_y in y..y + square_size {
-                for s_x in x..x + square_size {
-                    square_total += grid[s_y][s_x];
-                }
-            }
+    for y in 0..=GRID_SIZE - square_size {
+        for x in 0..=GRID_SIZE - square_size {
+            let (x2, y2) = (x + square_size, y + square_size);
+            let square_total =
+                sums[Point::new(x2, y2)] - sums[Point::new(x, y2)] - sums[Point::new(x2, y)]
+                    + sums[Point::new(x, y)];
 
             if square_total > best {
                 best = square_total;
@@ -78,39 +77,96 @@ fn square_total(grid: &Vec<Vec<i32>>, square_size: usize) -> (usize, usize, usiz
     (coords.0, coords.1, square_size, best)
 }
 
-fn populate_fuel_cells(grid: &mut Vec<Vec<i32>>) {
-    for (y, row) in grid.iter_mut().enumerate() {
-        let y_offset = y + 1;
-
-        for (x, cell) in row.iter_mut().enumerate() {
-            let x_offset = x + 1;
-
-            let rack_id = x_offset + 10;
-            let mut fuel = rack_id * y_offset;
-            fuel += GRID_SERIAL_NUMBER;
-            fuel *= rack_id;
-
-            if fuel < 100 {
-                *cell = 0;
-            } else {
-                fuel /= 100;
-                fuel %= 10;
-                fuel -= 5;
-                *cell = fuel as i32;
-            }
-        }
-    }
+// Grid coordinates are 0-based while the puzzle's start at 1
+fn fuel_cells(serial: i32) -> Grid<i32> {
+    Grid::from_fn(GRID_SIZE, GRID_SIZE, |p| {
+        power_level(Point::new(p.x + 1, p.y + 1), serial)
+    })
 }
 
-#[derive(Debug)]
-struct Coords {
-    x: u32,
-    y: u32,
-    size: u32,
+fn power_level(cell: Point, serial: i32) -> i32 {
+    // Wide enough that no serial number overflows
+    let rack_id = cell.x as i64 + 10;
+    let mut fuel = rack_id * cell.y as i64;
+    fuel += i64::from(serial);
+    fuel *= rack_id;
+
+    // Keep the hundreds digit
+    (fuel.rem_euclid(1000) / 100 - 5) as i32
 }
 
-impl Display for Coords {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({}, {}) Square Size: {}", self.x, self.y, self.size)
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::generators;
+
+    #[test]
+    fn test_power_level() {
+        assert_eq!(power_level(Point::new(3, 5), 8), 4);
+        assert_eq!(power_level(Point::new(122, 79), 57), -5);
+        assert_eq!(power_level(Point::new(217, 196), 39), 0);
+        assert_eq!(power_level(Point::new(101, 153), 71), 4);
+        assert_eq!(fuel_cells(8)[Point::new(2, 4)], 4);
+        assert_eq!(power_level(Point::new(300, 300), i32::MAX), 0);
+    }
+
+    #[test]
+    fn test_input() {
+        let serial = input_gen("18\n").unwrap();
+        assert_eq!(solve_part1(&serial), Answer::Coord(vec![33, 45]));
+        assert_eq!(solve_part1(&input_gen("42").unwrap()).to_string(), "21,61");
+
+        let err = input_gen("\n").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Missing("the grid serial number"));
+        assert_eq!(
+            input_gen("serial").unwrap_err().kind,
+            ErrorKind::InvalidNumber
+        );
+    }
+
+    // The squares used to stop short of the last row and column, and a
+    // best total started at 0 rather than the first square's
+    #[test]
+    fn test_edge_squares() {
+        let mut grid = Grid::new(GRID_SIZE, GRID_SIZE, -5);
+        grid[Point::new(GRID_SIZE - 1, GRID_SIZE - 1)] = 4;
+        let sums = summed_area(&grid);
+        assert_eq!(square_total(&sums, 1), (300, 300, 1, 4));
+        assert_eq!(square_total(&sums, 2), (299, 299, 2, -11));
+
+        let total = -5 * (GRID_SIZE * GRID_SIZE) as i32 + 9;
+        assert_eq!(square_total(&sums, GRID_SIZE), (1, 1, 300, total));
+    }
+
+    // Adds up every square cell by cell
+    fn brute_force(grid: &Grid<i32>, size: usize) -> (usize, usize, usize, i32) {
+        let mut best = None;
+        for y in 0..=GRID_SIZE - size {
+            for x in 0..=GRID_SIZE - size {
+                let total: i32 = grid
+                    .rows()
+                    .skip(y)
+                    .take(size)
+                    .map(|row| row[x..x + size].iter().sum::<i32>())
+                    .sum();
+                if best.is_none_or(|(_, _, _, b)| total > b) {
+                    best = Some((x + 1, y + 1, size, total));
+                }
+            }
+        }
+        best.unwrap()
+    }
+
+    #[test]
+    fn test_random_serials() {
+        generators::check(3, |rng| {
+            let serial = input_gen(&generators::day11(rng)).unwrap();
+            let grid = fuel_cells(serial);
+            let sums = summed_area(&grid);
+            for &size in &[1, 3, rng.between(4, 20) as usize, GRID_SIZE] {
+                assert_eq!(square_total(&sums, size), brute_force(&grid, size));
+            }
+        });
     }
 }
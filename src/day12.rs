@@ -1,5 +1,10 @@
+use crate::error::{self, Line, ParseError};
+use crate::grid::Grid;
+use crate::input;
 use regex::Regex;
-use std::{char::ParseCharError, str::FromStr};
+use std::str::FromStr;
+
+const DAY: u8 = 12;
 
 lazy_static! {
     static ref regexep: Regex = Regex::new(
@@ -11,67 +16,73 @@ lazy_static! {
     .unwrap();
 }
 
-const MAX_PLANTS: usize = 1001;
-
-fn input_gen(input: &str) -> Plants {
-    let mut lines = input.lines();
-    let mut initial_state = vec![false; MAX_PLANTS];
-    if let Some(l) = lines.next() {
-        let state = l.trim_start_matches("initial state:").trim();
-        let start = MAX_PLANTS / 2;
-        for (c, i) in state.chars().zip(start..) {
-            initial_state[i] = has_plant(c);
-        }
-    }
+const GENERATIONS: usize = 20;
+// Plants spread at most two pots a generation, and a pot's next state
+// depends on the two pots either side of it
+const PADDING: usize = 2 * GENERATIONS + 2;
+
+#[aoc_generator(day12)]
+pub fn input_gen(input: &str) -> Result<Plants, ParseError> {
+    let input = &input::normalize(input);
+    let mut lines = error::lines(DAY, input);
+    let l = lines
+        .next()
+        .ok_or_else(|| ParseError::missing(DAY, 1, "initial state"))?;
+    let state = l.text().trim_start_matches("initial state:").trim_start();
+    let offset = l.text().len() - state.len();
+    let pots = state
+        .trim_end()
+        .char_indices()
+        .map(|(idx, c)| has_plant(c).ok_or_else(|| l.unexpected_char(offset + idx, c)))
+        .collect::<Result<Vec<_>, _>>()?;
     lines.next();
 
-    let rules: Vec<_> = lines.map(|l| l.parse::<Rule>().unwrap()).collect();
+    let rules = lines.map(|l| l.parse_as()).collect::<Result<_, _>>()?;
 
-    Plants {
-        state: initial_state,
-        rules,
-    }
+    // Room for the plants to spread into either side
+    let width = pots.len() + 2 * PADDING;
+    let state = Grid::from_fn(width, 1, |p| {
+        p.x >= PADDING && pots.get(p.x - PADDING) == Some(&true)
+    });
+    Ok(Plants { state, rules })
 }
 
 #[aoc(day12, part1)]
-fn solve_part1(input: &str) -> i32 {
-    let mut p = input_gen(input);
+pub fn solve_part1(input: &Plants) -> i64 {
+    let mut p = input.clone();
 
-    for _ in 0..20 {
+    for _ in 0..GENERATIONS {
         p.next_gen();
     }
 
     p.sum()
 }
 
-fn has_plant(c: char) -> bool {
+fn has_plant(c: char) -> Option<bool> {
     match c {
-        '#' => true,
-        '.' => false,
-        _ => {
-            panic!("Unknown plant type");
-        }
+        '#' => Some(true),
+        '.' => Some(false),
+        _ => None,
     }
 }
 
+// The pots are a single row grid, with pot 0 `PADDING` pots in
 #[derive(Debug, Clone)]
-struct Plants {
-    state: Vec<bool>,
+pub struct Plants {
+    state: Grid<bool>,
     rules: Vec<Rule>,
 }
 
 impl Plants {
     fn next_gen(&mut self) {
-        let mut next = vec![false; MAX_PLANTS];
-        for i in 2..MAX_PLANTS - 2 {
-            next[i] = self.has_plant_in_next_gen(i);
-        }
-
-        self.state = next;
+        let width = self.state.width();
+        self.state = Grid::from_fn(width, 1, |p| {
+            (2..width - 2).contains(&p.x) && self.has_plant_in_next_gen(p.x)
+        });
     }
 
     fn has_plant_in_next_gen(&self, n: usize) -> bool {
-        let plants = &self.state[n - 2..=n + 2];
+        let plants = &self.state.row(0)[n - 2..=n + 2];
         for rule in self.rules.iter() {
             if rule.plants == plants {
                 return rule.next;
@@ -80,11 +91,11 @@ impl Plants {
         false
     }
 
-    fn sum(&self) -> i32 {
+    fn sum(&self) -> i64 {
         let mut sum = 0;
-        for (i, plant) in self.state.iter().enumerate() {
+        for (p, plant) in self.state.iter() {
             if *plant {
-                sum += i as i32 - (MAX_PLANTS / 2) as i32;
+                sum += p.x as i64 - PADDING as i64;
             }
         }
 
@@ -99,31 +110,43 @@ struct Rule {
 }
 
 impl FromStr for Rule {
-    type Err = ParseCharError;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let caps = regexep.captures(s).expect("Error while parsing for Rule");
-        let mut plants = [false; 5];
-        plants[0] = has_plant(caps["two_left"].parse()?);
-        plants[1] = has_plant(caps["left"].parse()?);
-        plants[2] = has_plant(caps["curr"].parse()?);
-        plants[3] = has_plant(caps["right"].parse()?);
-        plants[4] = has_plant(caps["two_right"].parse()?);
-        Ok(Rule {
-            plants,
-            next: has_plant(caps["next"].parse()?),
-        })
+        let caps = regexep
+            .captures(s)
+            .ok_or_else(|| Line::new(DAY, 1, s).malformed("a rule like `..#.. => #`"))?;
+        // The regex only lets through `#` and `.`
+        let plant = |name: &str| caps[name] == *"#";
+        let rule = Rule {
+            plants: [
+                plant("two_left"),
+                plant("left"),
+                plant("curr"),
+                plant("right"),
+                plant("two_right"),
+            ],
+            next: plant("next"),
+        };
+        // Every empty pot out to infinity would grow a plant
+        if rule.next && rule.plants == [false; 5] {
+            return Err(Line::new(DAY, 1, s).malformed("a rule that leaves empty pots empty"));
+        }
+        Ok(rule)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::ErrorKind;
+    use crate::generators;
+    use std::collections::HashSet;
 
     #[test]
     fn test_part1() {
-        let input = include_str!("../input/tests/d12.txt");
-        let sum = solve_part1(input);
+        let input = input_gen(include_str!("../input/tests/d12.txt")).unwrap();
+        let sum = solve_part1(&input);
 
         assert_eq!(sum, 325);
     }
@@ -131,10 +154,98 @@ mod test {
     #[test]
     fn test_input() {
         let input = include_str!("../input/tests/d12.txt");
-        let plants = input_gen(input);
-
-        println!("{:?}", plants);
+        let plants = input_gen(input).unwrap();
 
         assert_eq!(plants.rules.len(), 14);
     }
+
+    #[test]
+    fn test_bad_input() {
+        let err = input_gen("initial state: #..#x#").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 20));
+
+        let err = input_gen("initial state: #..#\n\n...## => #\n..#. => #").unwrap_err();
+        assert_eq!(err.line, 4);
+
+        let err = input_gen("initial state: #\n\n..... => #").unwrap_err();
+        assert_eq!(
+            (err.line, err.kind),
+            (
+                3,
+                ErrorKind::Malformed("a rule that leaves empty pots empty")
+            )
+        );
+    }
+
+    // The pots used to be a fixed 500 either side of pot 0
+    #[test]
+    fn test_long_state() {
+        // Every plant lives on, and one more grows two pots past the last
+        // plant on the side `grow` picks out
+        let rules = |grow: &str| {
+            let mut rules = format!("{} => #\n", grow);
+            for pattern in 0..32 {
+                let pots: String = (0..5)
+                    .map(|i| if pattern & (1 << i) != 0 { '#' } else { '.' })
+                    .collect();
+                if &pots[2..3] == "#" {
+                    rules += &format!("{} => #\n", pots);
+                }
+            }
+            rules
+        };
+        let input = format!("initial state: {}\n\n{}", "#".repeat(600), rules("#...."));
+        let grown: i64 = (1..=20).map(|gen| 599 + 2 * gen).sum();
+        assert_eq!(
+            solve_part1(&input_gen(&input).unwrap()),
+            (0..600).sum::<i64>() + grown
+        );
+
+        let input = format!("initial state: #\n\n{}", rules("....#"));
+        let grown: i64 = (1..=20).map(|gen| -2 * gen).sum();
+        assert_eq!(solve_part1(&input_gen(&input).unwrap()), grown);
+    }
+
+    // Keeps the numbers of the pots with plants
+    fn sum_after_20(input: &str) -> i64 {
+        let mut lines = input.lines();
+        let state = lines.next().unwrap().trim_start_matches("initial state: ");
+        let mut plants: HashSet<i64> = (0..)
+            .zip(state.chars())
+            .filter(|&(_, c)| c == '#')
+            .map(|(i, _)| i)
+            .collect();
+        let grows: HashSet<&str> = lines
+            .filter(|l| l.ends_with("=> #"))
+            .map(|l| &l[..5])
+            .collect();
+
+        for _ in 0..20 {
+            let (min, max) = (plants.iter().min(), plants.iter().max());
+            let (min, max) = match (min, max) {
+                (Some(min), Some(max)) => (*min, *max),
+                _ => break,
+            };
+            plants = (min - 2..=max + 2)
+                .filter(|pot| {
+                    let pots: String = (pot - 2..=pot + 2)
+                        .map(|p| if plants.contains(&p) { '#' } else { '.' })
+                        .collect();
+                    grows.contains(pots.as_str())
+                })
+                .collect();
+        }
+        plants.iter().sum()
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        generators::check(100, |rng| {
+            let input = generators::day12(rng);
+            assert_eq!(
+                solve_part1(&input_gen(&input).unwrap()),
+                sum_after_20(&input)
+            );
+        });
+    }
 }
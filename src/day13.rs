@@ -1,178 +1,175 @@
-use std::collections::HashMap;
+use crate::answer::Answer;
+use crate::error::ParseError;
+use crate::grid::{Grid, Point};
+use crate::input;
+use crate::render::{Frames, Image, RenderError, Rgb};
+use std::error::Error;
 use std::fmt::{self, Display};
 
-type Track = Vec<Vec<char>>;
+const DAY: u8 = 13;
+
+type Track = Grid<char>;
+
+// Short lines are padded with empty space. Part 1 needs two carts to
+// crash, with fewer it would never finish
+#[aoc_generator(day13)]
+pub fn input_gen(input: &str) -> Result<Track, ParseError> {
+    let input = &input::normalize(input);
+    let tracks = Grid::parse(DAY, input, ' ', |c| match c {
+        '|' | '-' | '/' | '\\' | '+' | ' ' => Some(c),
+        c if is_cart(c) => Some(c),
+        _ => None,
+    })?;
+    if tracks.iter().filter(|(_, &c)| is_cart(c)).count() < 2 {
+        return Err(ParseError::missing(
+            DAY,
+            tracks.height() + 1,
+            "at least two carts",
+        ));
+    }
+    Ok(tracks)
+}
 
-// Prints (x, y) coordinate of collision instead of returning
 #[aoc(day13, part1)]
-fn solve_part1(input: &str) -> Coords {
-    simulate_carts(input, true)
+pub fn solve_part1(input: &Track) -> Result<Coords, CartError> {
+    simulate_carts(input, true, &mut |_, _| ())
 }
 
 #[aoc(day13, part2)]
-fn solve_part2(input: &str) -> Coords {
-    simulate_carts(input, false)
+pub fn solve_part2(input: &Track) -> Result<Coords, CartError> {
+    simulate_carts(input, false, &mut |_, _| ())
 }
 
-// if stop_at_first_crash is true return position of the first crash
-// otherwise return the position of the last cart
-fn simulate_carts(input: &str, stop_at_first_crash: bool) -> Coords {
-    let mut tracks: Track = {
-        input
-            .lines()
-            .map(|l| l.chars().collect::<Vec<_>>())
-            .collect()
-    };
-    let mut carts: Vec<Cart> = init_carts(&mut tracks);
-    let mut collision_at = (0, 0);
-    let mut ticks = 0;
-    let is_cart = |c: char| match c {
-        '^' | '<' | '>' | 'v' => true,
-        _ => false,
-    };
-
-    let check_intersection_or_collision = |cart: &mut Cart| match cart.track {
-        '+' => {
-            match cart.intersection {
-                0 => cart.turn_left(),
-                2 => cart.turn_right(),
-                _ => (),
+// A frame for every tick of part 2, until at most one cart is left or one
+// runs off the track
+pub fn render(input: &Track, frames: &mut Frames) -> Result<(), RenderError> {
+    let mut result = Ok(());
+    let _ = simulate_carts(input, false, &mut |tracks, carts| {
+        if result.is_ok() {
+            let mut drawn = tracks.clone();
+            for c in carts.iter().filter(|c| !c.crashed) {
+                drawn[Point::new(c.col, c.row)] = c.symbol();
             }
-            cart.intersection += 1;
-            cart.intersection %= 3;
-            false
+            result = frames.push(Image::from_grid(&drawn, |&c| match c {
+                ' ' => Rgb::BLACK,
+                '+' => Rgb(200, 200, 200),
+                '^' | '<' | '>' | 'v' => Rgb(255, 210, 0),
+                _ => Rgb(110, 110, 110),
+            }));
         }
-        '^' | '<' | '>' | 'v' => {
-            println!("Collision at ({}, {})", cart.col, cart.row);
-            true
+    });
+    result
+}
+
+fn is_cart(c: char) -> bool {
+    matches!(c, '^' | '<' | '>' | 'v')
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CartError {
+    // Part 1 never sees a crash, which `input_gen` rules out
+    TooFewCarts,
+    // An even number of carts and none are left for part 2
+    AllCrashed,
+    // Where the cart was before its next step left the track
+    OffTrack(Coords),
+}
+
+impl Display for CartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartError::TooFewCarts => write!(f, "there are fewer than two carts"),
+            CartError::AllCrashed => write!(f, "every cart crashes"),
+            CartError::OffTrack(at) => write!(f, "the cart at {} runs off the track", at),
         }
+    }
+}
+
+impl Error for CartError {}
+
+// if stop_at_first_crash is true return position of the first crash
+// otherwise return the position of the last cart. on_tick sees the tracks,
+// with the carts taken off them, before every tick and once more at the end
+fn simulate_carts(
+    input: &Track,
+    stop_at_first_crash: bool,
+    on_tick: &mut dyn FnMut(&Track, &[Cart]),
+) -> Result<Coords, CartError> {
+    let mut carts: Vec<Cart> = init_carts(input);
+    let mut tracks = input.clone();
+    for c in &carts {
+        tracks[Point::new(c.col, c.row)] = c.track;
+    }
+    if stop_at_first_crash && carts.len() < 2 {
+        return Err(CartError::TooFewCarts);
+    }
 
-        _ => false,
-    };
     loop {
-        for y in 0..tracks.len() {
-            for x in 0..tracks[y].len() {
-                if is_cart(tracks[y][x]) {
-                    if let Some(c) = carts
-                        .iter_mut()
-                        .find(|c| c.col == x && c.row == y && !c.moved)
-                    {
-                        let mut found_collision = false;
-                        c.moved = true;
-
-                        tracks[y][x] = c.track;
-                        match c.direction {
-                            Direction::Up => {
-                                c.row -= 1;
-                                c.track = tracks[y - 1][x];
-                                match c.track {
-                                    '/' => c.direction = Direction::Right,
-                                    '\\' => c.direction = Direction::Left,
-                                    _ => found_collision = check_intersection_or_collision(c),
-                                }
-                            }
-                            Direction::Left => {
-                                c.col -= 1;
-                                c.track = tracks[y][x - 1];
-                                match c.track {
-                                    '/' => c.direction = Direction::Down,
-                                    '\\' => c.direction = Direction::Up,
-                                    _ => found_collision = check_intersection_or_collision(c),
-                                }
-                            }
-                            Direction::Down => {
-                                c.row += 1;
-                                c.track = tracks[y + 1][x];
-                                match c.track {
-                                    '/' => c.direction = Direction::Left,
-                                    '\\' => c.direction = Direction::Right,
-                                    _ => found_collision = check_intersection_or_collision(c),
-                                }
-                            }
-                            Direction::Right => {
-                                c.col += 1;
-                                c.track = tracks[y][x + 1];
-                                match c.track {
-                                    '/' => c.direction = Direction::Up,
-                                    '\\' => c.direction = Direction::Down,
-                                    _ => found_collision = check_intersection_or_collision(c),
-                                }
-                            }
-                            _ => (),
-                        }
-
-                        if found_collision {
-                            if stop_at_first_crash {
-                                return Coords {
-                                    col: c.col,
-                                    row: c.row,
-                                };
-                            }
-                            collision_at = (c.col, c.row);
-                            let col_cart = carts
-                                .iter()
-                                .find(|crt| {
-                                    crt.col == collision_at.0
-                                        && crt.row == collision_at.1
-                                        && !is_cart(crt.track)
-                                })
-                                .unwrap();
-                            tracks[collision_at.1][collision_at.0] = col_cart.track;
-                            carts.retain(|cart| {
-                                !(cart.col == collision_at.0 && cart.row == collision_at.1)
-                            });
-                        } else {
-                            tracks[c.row][c.col] = match c.direction {
-                                Direction::Up => '^',
-                                Direction::Left => '<',
-                                Direction::Down => 'v',
-                                Direction::Right => '>',
-                            };
-                        }
-                    }
+        on_tick(&tracks, &carts);
+        carts.sort_by_key(|c| (c.row, c.col));
+        for i in 0..carts.len() {
+            if carts[i].crashed {
+                continue;
+            }
+            carts[i].step(&tracks)?;
+
+            let at = (carts[i].col, carts[i].row);
+            let hit = (0..carts.len())
+                .find(|&j| j != i && !carts[j].crashed && (carts[j].col, carts[j].row) == at);
+            if let Some(j) = hit {
+                if stop_at_first_crash {
+                    on_tick(&tracks, &carts);
+                    return Ok(Coords {
+                        col: at.0,
+                        row: at.1,
+                    });
                 }
+                carts[i].crashed = true;
+                carts[j].crashed = true;
             }
         }
-        if carts.len() == 1 {
-            let c = &carts[0];
-            println!("Last cart at: ({}, {})", c.col, c.row);
-            return Coords {
-                col: c.col,
-                row: c.row,
-            };
-        }
-        for cart in carts.iter_mut() {
-            cart.moved = false;
+
+        carts.retain(|c| !c.crashed);
+        if carts.len() <= 1 {
+            on_tick(&tracks, &carts);
+            return carts
+                .first()
+                .map(|c| Coords {
+                    col: c.col,
+                    row: c.row,
+                })
+                .ok_or(CartError::AllCrashed);
         }
-        ticks += 1;
     }
-
-    Coords { row: 0, col: 0 }
 }
 
-#[derive(Debug, PartialEq)]
-struct Coords {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coords {
     row: usize,
     col: usize,
 }
 
 impl Display for Coords {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "({}, {})", self.col, self.row)
+        write!(f, "{},{}", self.col, self.row)
+    }
+}
+
+impl From<Coords> for Answer {
+    fn from(c: Coords) -> Self {
+        Answer::Coord(vec![c.col as i64, c.row as i64])
     }
 }
 
-fn init_carts(tracks: &mut Track) -> Vec<Cart> {
+fn init_carts(tracks: &Track) -> Vec<Cart> {
     let mut carts: Vec<Cart> = vec![];
-    for (y, row) in tracks.iter_mut().enumerate() {
-        for (x, col) in row.iter_mut().enumerate() {
-            match *col {
-                '^' => carts.push(Cart::new(Direction::Up, y, x)),
-                '<' => carts.push(Cart::new(Direction::Left, y, x)),
-                '>' => carts.push(Cart::new(Direction::Right, y, x)),
-                'v' => carts.push(Cart::new(Direction::Down, y, x)),
-                _ => (),
-            }
+    for (p, c) in tracks.iter() {
+        match *c {
+            '^' => carts.push(Cart::new(Direction::Up, p.y, p.x)),
+            '<' => carts.push(Cart::new(Direction::Left, p.y, p.x)),
+            '>' => carts.push(Cart::new(Direction::Right, p.y, p.x)),
+            'v' => carts.push(Cart::new(Direction::Down, p.y, p.x)),
+            _ => (),
         }
     }
 
@@ -188,7 +185,7 @@ struct Cart {
     intersection: u8,
     row: usize,
     col: usize,
-    moved: bool,
+    crashed: bool,
 }
 
 impl Cart {
@@ -204,10 +201,61 @@ impl Cart {
             intersection: 0,
             row,
             col,
-            moved: false,
+            crashed: false,
         }
     }
 
+    fn symbol(&self) -> char {
+        match self.direction {
+            Direction::Up => '^',
+            Direction::Left => '<',
+            Direction::Down => 'v',
+            Direction::Right => '>',
+        }
+    }
+
+    // Moves one square and turns to follow the track there. Off the edge
+    // of the map or onto empty space is an error, and the cart stays put
+    fn step(&mut self, tracks: &Track) -> Result<(), CartError> {
+        use self::Direction::*;
+        let next = match self.direction {
+            Up => self.row.checked_sub(1).map(|row| Point::new(self.col, row)),
+            Left => self.col.checked_sub(1).map(|col| Point::new(col, self.row)),
+            Down => Some(Point::new(self.col, self.row + 1)),
+            Right => Some(Point::new(self.col + 1, self.row)),
+        };
+        let (next, track) = match next.and_then(|p| Some((p, *tracks.get(p)?))) {
+            Some((p, t)) if t != ' ' => (p, t),
+            _ => {
+                return Err(CartError::OffTrack(Coords {
+                    col: self.col,
+                    row: self.row,
+                }))
+            }
+        };
+
+        self.col = next.x;
+        self.row = next.y;
+        self.track = track;
+        match (track, self.direction) {
+            ('/', Up) | ('\\', Down) => self.direction = Right,
+            ('/', Down) | ('\\', Up) => self.direction = Left,
+            ('/', Left) | ('\\', Right) => self.direction = Down,
+            ('/', Right) | ('\\', Left) => self.direction = Up,
+            ('+', _) => {
+                match self.intersection {
+                    0 => self.turn_left(),
+                    2 => self.turn_right(),
+                    _ => (),
+                }
+                self.intersection += 1;
+                self.intersection %= 3;
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
     fn turn_left(&mut self) {
         use self::Direction::*;
         let d = match self.direction {
@@ -233,7 +281,7 @@ impl Cart {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 enum Direction {
     Up,
     Left,
@@ -244,20 +292,62 @@ enum Direction {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::ErrorKind;
 
     #[test]
     fn test_part1() {
-        let input = include_str!("../input/tests/d13.txt");
+        let input = input_gen(include_str!("../input/tests/d13.txt")).unwrap();
         let coords = Coords { col: 7, row: 3 };
-        assert_eq!(solve_part1(input), coords);
+        assert_eq!(solve_part1(&input), Ok(coords));
     }
 
     #[test]
     fn test_part2() {
-        let input = include_str!("../input/tests/d13-2.txt");
+        let input = input_gen(include_str!("../input/tests/d13-2.txt")).unwrap();
         let coords = Coords { col: 6, row: 4 };
 
-        assert_eq!(solve_part2(input), coords);
+        assert_eq!(solve_part2(&input), Ok(coords));
+    }
+
+    #[test]
+    fn test_bad_tracks() {
+        let err = input_gen("").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Missing("at least two carts"));
+        let err = input_gen("/->-\\\n|   |\n\\---/").unwrap_err();
+        assert_eq!(
+            (err.line, err.kind),
+            (4, ErrorKind::Missing("at least two carts"))
+        );
+        let err = input_gen("->-<-\n-x--").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 2));
+
+        // Two carts crash into each other and leave nothing for part 2
+        let input = input_gen("->-<-").unwrap();
+        assert_eq!(solve_part1(&input), Ok(Coords { col: 2, row: 0 }));
+        assert_eq!(solve_part2(&input), Err(CartError::AllCrashed));
+
+        // Off the edge of the map, and onto empty space
+        let input = input_gen("<>").unwrap();
+        let at = Coords { col: 0, row: 0 };
+        assert_eq!(solve_part1(&input), Err(CartError::OffTrack(at)));
+        let input = input_gen("-> <-").unwrap();
+        let at = Coords { col: 1, row: 0 };
+        assert_eq!(solve_part2(&input), Err(CartError::OffTrack(at)));
+    }
+
+    // Several pairs crashing on the same tick, and a cart driving through
+    // a crash site right after it's cleared
+    #[test]
+    fn test_crashes_in_one_tick() {
+        let input = "><><>--";
+        let tracks = input_gen(input).unwrap();
+        assert_eq!(solve_part1(&tracks), Ok(Coords { col: 1, row: 0 }));
+        assert_eq!(solve_part2(&tracks), Ok(Coords { col: 5, row: 0 }));
+
+        let input = "-->-<<";
+        let tracks = input_gen(input).unwrap();
+        assert_eq!(solve_part1(&tracks), Ok(Coords { col: 3, row: 0 }));
+        assert_eq!(solve_part2(&tracks), Ok(Coords { col: 4, row: 0 }));
     }
 
     #[test]
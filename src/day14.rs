@@ -1,13 +1,24 @@
+use crate::error::{Line, ParseError};
+use crate::input;
 use std::char;
 
+const DAY: u8 = 14;
+
+#[aoc_generator(day14)]
+pub fn input_gen(input: &str) -> Result<usize, ParseError> {
+    let input = &input::normalize(input);
+    let line = Line::new(DAY, 1, input);
+    line.parse(input.trim())
+}
+
 #[aoc(day14, part1)]
-fn solve_part1(input: &str) -> String {
-    let NUM_RECIPES: usize = input.trim().parse().unwrap();
+pub fn solve_part1(input: &usize) -> String {
+    let num_recipes = *input;
     let mut recipes = vec![3, 7];
     let (mut elf_1, mut elf_2) = (0, 1);
     loop {
         let mut sum = recipes[elf_1] + recipes[elf_2];
-        
+
         if sum < 10 {
             recipes.push(sum);
         } else {
@@ -18,72 +29,49 @@ fn solve_part1(input: &str) -> String {
             recipes.push(second);
         }
 
-        elf_1 += (1 + recipes[elf_1]);
+        elf_1 += 1 + recipes[elf_1];
         elf_1 %= recipes.len();
-        elf_2 += (1 + recipes[elf_2]);
+        elf_2 += 1 + recipes[elf_2];
         elf_2 %= recipes.len();
-        if recipes.len() > NUM_RECIPES + 10 {
+        if recipes.len() > num_recipes + 10 {
             break;
         }
     }
-    
+
     let mut result = String::new();
-    for r in recipes.iter().skip(NUM_RECIPES).take(10) {
-       result.push(char::from_digit(*r as u32, 10).unwrap());
+    for r in recipes.iter().skip(num_recipes).take(10) {
+        result.push(char::from_digit(*r as u32, 10).unwrap());
     }
     result
 }
 
 #[aoc(day14, part2)]
-fn solve_part2(input: &str) -> u32 {
-    let NUM_RECIPES: usize = input.trim().parse().unwrap();
+pub fn solve_part2(input: &usize) -> u32 {
+    let pattern = to_digits(*input);
     let mut recipes = vec![3, 7];
     let (mut elf_1, mut elf_2) = (0, 1);
-    let mut curr_digits = vec![];
-    let pattern = to_digits(NUM_RECIPES);
-    let mut p_i = 0;
-    let mut r_i = 0;
-    let mut i = 0;
+    // Every position is checked once there are enough recipes after it, so
+    // a match starting inside a partial match isn't missed
+    let mut start = 0;
     loop {
-        let mut sum = recipes[elf_1] + recipes[elf_2];
-        
-        curr_digits.push(recipes[i]);
-        if sum < 10 {
-            recipes.push(sum);
-        } else {
-            let second = sum % 10;
-            sum /= 10;
-            let first = sum % 10;
-            recipes.push(first);
-            recipes.push(second);
+        while start + pattern.len() <= recipes.len() {
+            if recipes[start..start + pattern.len()] == pattern[..] {
+                return start as u32;
+            }
+            start += 1;
+        }
+
+        let sum = recipes[elf_1] + recipes[elf_2];
+        if sum >= 10 {
+            recipes.push(sum / 10);
         }
+        recipes.push(sum % 10);
 
-        elf_1 += (1 + recipes[elf_1]);
+        elf_1 += 1 + recipes[elf_1];
         elf_1 %= recipes.len();
-        elf_2 += (1 + recipes[elf_2]);
+        elf_2 += 1 + recipes[elf_2];
         elf_2 %= recipes.len();
-
-        if curr_digits[r_i] == pattern[p_i] {
-            if curr_digits.len() == pattern.len() {
-                i -= pattern.len() - 1;
-                break;
-            }
-            r_i += 1;
-            p_i += 1;
-        } else {
-            curr_digits.clear();
-            p_i = 0;
-            r_i = 0;
-            if recipes[i] == pattern[p_i] {
-                curr_digits.push(recipes[i]);
-                r_i += 1;
-                p_i += 1;
-            }
-        }
-        i += 1;
     }
-    
-    i as u32
 }
 
 fn to_digits(n: usize) -> Vec<usize> {
@@ -99,13 +87,27 @@ mod test {
 
     #[test]
     fn test_part1() {
-        assert_eq!(&solve_part1("18"), "9251071085");
-        assert_eq!(&solve_part1("2018"), "5941429882");
+        assert_eq!(&solve_part1(&18), "9251071085");
+        assert_eq!(&solve_part1(&input_gen("2018\n").unwrap()), "5941429882");
     }
 
     #[test]
     fn test_part2() {
-        assert_eq!(solve_part2("51589"), 9);
-        assert_eq!(solve_part2("59414"), 2018);
+        assert_eq!(solve_part2(&51589), 9);
+        assert_eq!(solve_part2(&59414), 2018);
+    }
+
+    // A mismatch used to start matching again from the recipe that
+    // didn't match, missing the `1012` that starts halfway into `1010`
+    #[test]
+    fn test_part2_overlapping() {
+        assert_eq!(solve_part1(&0), "3710101245");
+        assert_eq!(solve_part2(&1012), 4);
+    }
+
+    #[test]
+    fn test_bad_input() {
+        let err = input_gen("  20l8").unwrap_err();
+        assert_eq!((err.line, err.column, err.text.as_str()), (1, 3, "20l8"));
     }
 }
@@ -1,18 +1,26 @@
+use crate::error::ParseError;
+use crate::grid::{Grid, Point};
+use crate::input;
+use crate::render::{Frames, Image, RenderError, Rgb};
 use slotmap::{DefaultKey, SecondaryMap, SlotMap};
-use std::cmp::{Ord, Ordering, PartialOrd};
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
-use std::{char::ParseCharError, convert::From, str::FromStr};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::str::FromStr;
 
+type Coord = Point;
+
+const DAY: u8 = 15;
 const HIT_POINTS: i32 = 200;
 const ATTACK_POWER: i32 = 3;
 
-fn input_gen(input: &str) -> Cave {
-    input.parse().unwrap()
+#[aoc_generator(day15)]
+pub fn input_gen(input: &str) -> Result<Cave, ParseError> {
+    input::normalize(input).parse()
 }
 
 #[aoc(day15, part1)]
-fn solve_part1(input: &str) -> i32 {
-    let mut cave = input_gen(input);
+pub fn solve_part1(input: &Cave) -> i32 {
+    let mut cave = input.clone();
     let mut round = 0;
     while !cave.step() {
         round += 1;
@@ -22,11 +30,11 @@ fn solve_part1(input: &str) -> i32 {
 }
 
 #[aoc(day15, part2)]
-fn solve_part2(input: &str) -> i32 {
+pub fn solve_part2(input: &Cave) -> i32 {
     let mut elf_attack = 4;
-    let mut outcome = 0;
+    let outcome;
     loop {
-        let mut cave = input_gen(input);
+        let mut cave = input.clone();
         let mut round = 0;
         let start_count = cave.elf_count();
         cave.elf_attack = elf_attack;
@@ -43,9 +51,21 @@ fn solve_part2(input: &str) -> i32 {
     outcome
 }
 
-struct Cave {
+// A frame for every round of the part 1 fight, units fading as they
+// lose hit points
+pub fn render(input: &Cave, frames: &mut Frames) -> Result<(), RenderError> {
+    let mut cave = input.clone();
+    loop {
+        frames.push(cave.image())?;
+        if cave.step() {
+            return frames.push(cave.image());
+        }
+    }
+}
+
+pub struct Cave {
     elf_attack: i32,
-    adjacency_list: BTreeMap<Coord, Vec<Coord>>,
+    tiles: Grid<Tile>,
     occupied: BTreeMap<Coord, DefaultKey>,
     units: SlotMap<DefaultKey, Unit>,
     unit_tiles: SecondaryMap<DefaultKey, Coord>,
@@ -53,18 +73,17 @@ struct Cave {
 
 impl Cave {
     fn step(&mut self) -> bool {
-        use self::Side::*;
         let unit_coords: Vec<(Coord, DefaultKey)> =
             self.occupied.iter().map(|(&k, &v)| (k, v)).collect();
-        for (mut tile, u) in unit_coords.into_iter() {
-            if let None = self.units.get(u) {
+        for (tile, u) in unit_coords.into_iter() {
+            if self.units.get(u).is_none() {
                 continue;
             }
-            let mut unit = self.units[u];
+            let unit = self.units[u];
             if unit.dead {
                 continue;
             }
-            let mut targets = self.find_targets(&unit);
+            let targets = self.find_targets(&unit);
 
             if targets.is_empty() {
                 return true;
@@ -92,7 +111,7 @@ impl Cave {
                     Side::Goblin => ATTACK_POWER,
                     Side::Elf => self.elf_attack,
                 };
-                let mut enemy_unit = self.units.get_mut(*enemy_id).unwrap();
+                let enemy_unit = self.units.get_mut(*enemy_id).unwrap();
                 enemy_unit.hit_points -= attack_power;
 
                 if enemy_unit.hit_points <= 0 {
@@ -115,20 +134,16 @@ impl Cave {
     }
 
     fn can_attack_targets(&self, unit_id: &DefaultKey, targets_pos: &[Coord]) -> Option<Coord> {
-        let unit = self.units[*unit_id];
         let unit_pos = self.unit_tiles[*unit_id];
         let mut enemy_pos: Option<Coord> = None;
         let mut min_health = HIT_POINTS;
         for pos in targets_pos {
-            if self.adjacency_list[&unit_pos].contains(pos) {
+            if self.adjacent(unit_pos).any(|a| a == *pos) {
                 if let Some(e_pos) = enemy_pos {
                     let enemy_unit = self.units[self.occupied[pos]];
-                    match (enemy_unit.hit_points, *pos).cmp(&(min_health, e_pos)) {
-                        Ordering::Less => {
-                            min_health = enemy_unit.hit_points;
-                            enemy_pos = Some(*pos);
-                        }
-                        _ => (),
+                    if (enemy_unit.hit_points, *pos).cmp(&(min_health, e_pos)) == Ordering::Less {
+                        min_health = enemy_unit.hit_points;
+                        enemy_pos = Some(*pos);
                     }
                 } else {
                     enemy_pos = Some(*pos);
@@ -141,26 +156,29 @@ impl Cave {
         enemy_pos
     }
 
+    // A step towards the nearest square in range of a target. Ties are
+    // broken in reading order, first on the square and then on the step
     fn move_to(&self, unit_pos: &Coord, targets_pos: &[Coord]) -> Option<Coord> {
-        if !self.in_range(unit_pos, targets_pos) {
-            let mut paths = self.paths_to_targets(unit_pos.to_owned(), targets_pos);
-            if paths.is_empty() {
-                return None;
-            }
-            let mut sorted: Vec<_> = paths
-                .iter()
-                .map(|path| (path.last().unwrap().clone(), path[1].clone()))
-                .collect();
-            sorted.sort_unstable();
-            return Some(sorted[0].1);
+        if self.in_range(unit_pos, targets_pos) {
+            return None;
         }
 
-        None
+        let from_unit = self.distances(*unit_pos);
+        let (_, square) = targets_pos
+            .iter()
+            .flat_map(|t| self.adjacent(*t))
+            .filter_map(|a| from_unit.get(&a).map(|d| (*d, a)))
+            .min()?;
+        let to_square = self.distances(square);
+        self.adjacent(*unit_pos)
+            .filter_map(|a| to_square.get(&a).map(|d| (*d, a)))
+            .min()
+            .map(|(_, step)| step)
     }
 
     fn in_range(&self, unit_pos: &Coord, targets_pos: &[Coord]) -> bool {
         for pos in targets_pos {
-            if self.adjacency_list[unit_pos].contains(pos) {
+            if self.adjacent(*unit_pos).any(|a| a == *pos) {
                 return true;
             }
         }
@@ -168,65 +186,47 @@ impl Cave {
         false
     }
 
-    fn paths_to_targets<'t>(&self, tile_pos: Coord, targets_pos: &'t [Coord]) -> Vec<Vec<Coord>> {
-        let mut seen: HashSet<_> = self
-            .occupied
-            .keys()
-            .filter(|c| **c != tile_pos)
-            .cloned()
-            .collect();
-        let mut min_len_to_target = None;
-        let mut results = Vec::new();
+    // How many steps it takes to reach each tile from `tile`, going around
+    // walls and units
+    fn distances(&self, tile: Coord) -> HashMap<Coord, usize> {
+        let mut distances = HashMap::new();
+        distances.insert(tile, 0);
         let mut q = VecDeque::new();
-        q.push_front((0, vec![tile_pos.clone()]));
-        while !q.is_empty() {
-            let (path_len, mut path) = q.pop_back().unwrap();
-            let tile = path.last().unwrap();
-
-            if let Some(best) = min_len_to_target {
-                if path_len > best {
-                    return results;
+        q.push_back(tile);
+        while let Some(curr) = q.pop_front() {
+            let next = distances[&curr] + 1;
+            for adjacent in self.adjacent(curr) {
+                if !self.occupied.contains_key(&adjacent) && !distances.contains_key(&adjacent) {
+                    distances.insert(adjacent, next);
+                    q.push_back(adjacent);
                 }
             }
+        }
 
-            if self.adjacency_list[&tile]
-                .iter()
-                .any(|a| targets_pos.contains(a))
-            {
-                let mut len = path_len + 1;
+        distances
+    }
 
-                let curr_target_tile = self.adjacency_list[&tile]
-                    .iter()
-                    .cloned()
-                    .find(|a| targets_pos.contains(a))
-                    .unwrap();
-                path.push(curr_target_tile.clone());
-                
-                if let Some(best) = min_len_to_target {
-                    if len > best {
-                        return results;
-                    }
-                } else {
-                    min_len_to_target = Some(len);
-                    results.push(path);
-                }
-                continue;
-            }
+    // Open tiles next to `tile`, in reading order
+    fn adjacent(&self, tile: Coord) -> impl Iterator<Item = Coord> + '_ {
+        self.tiles
+            .neighbours4(tile)
+            .filter(move |&a| self.tiles[a] == Tile::Open)
+    }
 
-            if seen.contains(&tile) {
-                continue;
-            }
-            seen.insert(*tile);
-            for adjacent in &self.adjacency_list[&tile] {
-                if !seen.contains(adjacent) {
-                    let mut p = path.clone();
-                    p.push(adjacent.to_owned());
-                    q.push_front((path_len + 1, p));
-                }
-            }
+    fn image(&self) -> Image {
+        let mut colours = self.tiles.map(|t| match t {
+            Tile::Wall => Rgb(70, 60, 50),
+            Tile::Open => Rgb(20, 20, 20),
+        });
+        for (&tile, &u) in &self.occupied {
+            let unit = self.units[u];
+            let colour = match unit.side {
+                Side::Elf => Rgb(40, 220, 60),
+                Side::Goblin => Rgb(230, 40, 40),
+            };
+            colours[tile] = colour.fade((55 + 200 * unit.hit_points / HIT_POINTS) as u8);
         }
-
-        results
+        Image::from_grid(&colours, |&c| c)
     }
 
     fn sum_units_hp(&self) -> i32 {
@@ -246,62 +246,62 @@ impl Cave {
     }
 }
 
+// slotmap's `SecondaryMap` isn't `Clone`, but a cloned `SlotMap` keeps
+// its keys so the unit positions can be copied across one by one
+impl Clone for Cave {
+    fn clone(&self) -> Self {
+        let mut unit_tiles = SecondaryMap::new();
+        for (k, tile) in self.unit_tiles.iter() {
+            unit_tiles.insert(k, *tile);
+        }
+
+        Cave {
+            elf_attack: self.elf_attack,
+            tiles: self.tiles.clone(),
+            occupied: self.occupied.clone(),
+            units: self.units.clone(),
+            unit_tiles,
+        }
+    }
+}
+
 impl FromStr for Cave {
-    type Err = ParseCharError;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut adjacency_list: BTreeMap<_, Vec<_>> = BTreeMap::new();
-        let grid: Vec<Vec<_>> = s.lines().map(|l| l.trim().chars().collect()).collect();
+        // Whitespace around the map is treated as more rock
+        let map = Grid::parse(DAY, s, '#', |c| match c {
+            '#' | '.' | 'G' | 'E' => Some(c),
+            c if c.is_whitespace() => Some('#'),
+            _ => None,
+        })?;
         let mut occupied = BTreeMap::new();
         let mut units = SlotMap::new();
         let mut unit_tiles = SecondaryMap::new();
-        let n = grid.len();
-        for y in 0..n {
-            let m = grid[y].len();
-            for x in 0..m {
-                if grid[y][x] != '#' {
-                    match grid[y][x] {
-                        'G' => {
-                            let k = units.insert(Unit::new(Side::Goblin));
-                            unit_tiles.insert(k, (x, y).into());
-                            occupied.insert((x, y).into(), k);
-                        }
-                        'E' => {
-                            let k = units.insert(Unit::new(Side::Elf));
-                            unit_tiles.insert(k, (x, y).into());
-                            occupied.insert((x, y).into(), k);
-                        }
-                        '.' => (),
-                        _ => panic!("Unexpected char"),
-                    }
-                    if x + 1 < m && grid[y][x + 1] != '#' {
-                        adjacency_list
-                            .entry((x, y).into())
-                            .or_insert(Vec::new())
-                            .push((x + 1, y).into());
-                        adjacency_list
-                            .entry((x + 1, y).into())
-                            .or_insert(Vec::new())
-                            .push((x, y).into());
-                    }
+        for (tile, c) in map.iter() {
+            let side = match c {
+                'G' => Side::Goblin,
+                'E' => Side::Elf,
+                _ => continue,
+            };
+            let k = units.insert(Unit::new(side));
+            unit_tiles.insert(k, tile);
+            occupied.insert(tile, k);
+        }
 
-                    if y + 1 < n && grid[y + 1][x] != '#' {
-                        adjacency_list
-                            .entry((x, y).into())
-                            .or_insert(Vec::new())
-                            .push((x, y + 1).into());
-                        adjacency_list
-                            .entry((x, y + 1).into())
-                            .or_insert(Vec::new())
-                            .push((x, y).into());
-                    }
-                }
-            }
+        // With nobody to fight the combat would never end
+        let sides: Vec<Side> = units.values().map(|u| u.side).collect();
+        if !sides.contains(&Side::Elf) || !sides.contains(&Side::Goblin) {
+            return Err(ParseError::missing(
+                DAY,
+                map.height() + 1,
+                "both an elf and a goblin",
+            ));
         }
 
         Ok(Cave {
             elf_attack: ATTACK_POWER,
-            adjacency_list,
+            tiles: map.map(|&c| if c == '#' { Tile::Wall } else { Tile::Open }),
             occupied,
             units,
             unit_tiles,
@@ -309,27 +309,10 @@ impl FromStr for Cave {
     }
 }
 
-#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
-struct Coord {
-    x: usize,
-    y: usize,
-}
-
-impl PartialOrd for Coord {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        (self.y, self.x).partial_cmp(&(other.y, other.x))
-    }
-}
-impl Ord for Coord {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
-    }
-}
-
-impl From<(usize, usize)> for Coord {
-    fn from(t: (usize, usize)) -> Self {
-        Coord { x: t.0, y: t.1 }
-    }
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Tile {
+    Wall,
+    Open,
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -340,16 +323,7 @@ enum Side {
 
 impl Unit {
     fn oppose(&self, other: &Unit) -> bool {
-        match self.side {
-            Side::Goblin => match other.side {
-                Side::Elf => true,
-                _ => false,
-            },
-            Side::Elf => match other.side {
-                Side::Goblin => true,
-                _ => false,
-            },
-        }
+        self.side != other.side
     }
 }
 
@@ -357,7 +331,6 @@ impl Unit {
 struct Unit {
     side: Side,
     hit_points: i32,
-    acted_this_round: bool,
     dead: bool,
 }
 
@@ -366,7 +339,6 @@ impl Unit {
         Unit {
             side,
             hit_points: HIT_POINTS,
-            acted_this_round: false,
             dead: false,
         }
     }
@@ -375,26 +347,180 @@ impl Unit {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::ErrorKind;
+    use crate::generators;
 
     #[test]
     fn test_input() {
-        let cave = input_gen(include_str!("../input/tests/d15.txt"));
+        let cave = input_gen(include_str!("../input/tests/d15.txt")).unwrap();
         assert_eq!(cave.units.len(), 9);
         assert_eq!(cave.elf_count(), 6);
     }
 
     #[test]
     fn test_part1() {
-        assert_eq!(solve_part1(include_str!("../input/tests/d15.txt")), 39514);
-        assert_eq!(solve_part1(include_str!("../input/tests/d15-2.txt")), 18740);
-        assert_eq!(solve_part1(include_str!("../input/tests/d15-3.txt")), 36334);
-        assert_eq!(solve_part1(include_str!("../input/tests/d15-4.txt")), 28944);
+        let part1 = |input| solve_part1(&input_gen(input).unwrap());
+        assert_eq!(part1(include_str!("../input/tests/d15.txt")), 39514);
+        assert_eq!(part1(include_str!("../input/tests/d15-2.txt")), 18740);
+        assert_eq!(part1(include_str!("../input/tests/d15-3.txt")), 36334);
+        assert_eq!(part1(include_str!("../input/tests/d15-4.txt")), 28944);
     }
 
     #[test]
     fn test_part2() {
-        let cave = input_gen(include_str!("../input/tests/d15-5.txt"));
+        let cave = input_gen(include_str!("../input/tests/d15-5.txt")).unwrap();
         assert_eq!(cave.elf_count(), 2);
-        assert_eq!(solve_part2(include_str!("../input/tests/d15-5.txt")), 4988);
+        assert_eq!(solve_part2(&cave), 4988);
+    }
+
+    // Where the elf in `map` steps to when it's its turn
+    fn elf_step(map: &str) -> Option<Point> {
+        let cave: Cave = map.parse().unwrap();
+        let (elf, goblins): (Vec<_>, Vec<_>) = cave
+            .occupied
+            .iter()
+            .map(|(&p, &k)| (p, cave.units[k].side))
+            .partition(|(_, side)| *side == Side::Elf);
+        let goblins: Vec<Point> = goblins.into_iter().map(|(p, _)| p).collect();
+        cave.move_to(&elf[0].0, &goblins)
+    }
+
+    // Moves used to head for whichever square in range the search reached
+    // first, rather than the nearest one in reading order
+    #[test]
+    fn test_move() {
+        let map = "#######\n#E..G.#\n#...#.#\n#.G.#G#\n#######";
+        assert_eq!(elf_step(map), Some(Point::new(2, 1)));
+        let map = "#######\n#.E...#\n#.....#\n#...G.#\n#######";
+        assert_eq!(elf_step(map), Some(Point::new(3, 1)));
+
+        // (5, 3) and (2, 4) are both two steps away, and (5, 3) comes first
+        let map = [
+            "#########",
+            "#.......#",
+            "#.......#",
+            "#..E..G.#",
+            "#.......#",
+            "#.G.....#",
+            "#########",
+        ];
+        assert_eq!(elf_step(&map.join("\n")), Some(Point::new(4, 3)));
+    }
+
+    #[test]
+    fn test_bad_cave() {
+        let err = input_gen("#####\n#.G.#\n#E?.#\n#####")
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!((err.day, err.line, err.column), (15, 3, 3));
+
+        for cave in &["", "#####\n#.G.#\n#####", "#####\n#E..#\n#####"] {
+            let err = input_gen(cave).map(|_| ()).unwrap_err();
+            assert_eq!(err.kind, ErrorKind::Missing("both an elf and a goblin"));
+        }
+    }
+
+    fn adjacent(a: Point, b: Point) -> bool {
+        a.x.max(b.x) - a.x.min(b.x) + a.y.max(b.y) - a.y.min(b.y) == 1
+    }
+
+    // Distances from `from` to every open square it can reach
+    fn distances(
+        walls: &Grid<bool>,
+        units: &[(Point, bool, i32)],
+        from: Point,
+    ) -> Grid<Option<usize>> {
+        let mut distances = walls.map(|_| None);
+        distances[from] = Some(0);
+        let mut queue = VecDeque::from(vec![from]);
+        while let Some(p) = queue.pop_front() {
+            for n in walls.neighbours4(p) {
+                let blocked = walls[n] || units.iter().any(|u| u.0 == n && u.2 > 0);
+                if !blocked && distances[n].is_none() {
+                    distances[n] = distances[p].map(|d| d + 1);
+                    queue.push_back(n);
+                }
+            }
+        }
+        distances
+    }
+
+    // Plays out the combat straight from the rules, returning the outcome
+    // and whether every elf survived
+    fn combat(map: &str, elf_attack: i32) -> (i32, bool) {
+        let map = Grid::parse(DAY, map, '#', Some).unwrap();
+        let walls = map.map(|&c| c == '#');
+        // Position, whether it's an elf and hit points
+        let mut units: Vec<(Point, bool, i32)> = map
+            .iter()
+            .filter(|(_, &c)| c == 'E' || c == 'G')
+            .map(|(p, &c)| (p, c == 'E', HIT_POINTS))
+            .collect();
+        let elves = units.iter().filter(|u| u.1).count();
+
+        for round in 0.. {
+            units.retain(|u| u.2 > 0);
+            units.sort_by_key(|u| u.0);
+            for i in 0..units.len() {
+                if units[i].2 <= 0 {
+                    continue;
+                }
+                let (pos, elf, _) = units[i];
+                let enemies: Vec<usize> = (0..units.len())
+                    .filter(|&j| units[j].1 != elf && units[j].2 > 0)
+                    .collect();
+                if enemies.is_empty() {
+                    let hp: i32 = units.iter().map(|u| u.2.max(0)).sum();
+                    let survived = units.iter().filter(|u| u.1 && u.2 > 0).count() == elves;
+                    return (round * hp, survived);
+                }
+
+                if !enemies.iter().any(|&j| adjacent(pos, units[j].0)) {
+                    // Nearest square in range of an enemy, then the first step
+                    // towards it, both in reading order
+                    let from_unit = distances(&walls, &units, pos);
+                    let target = walls
+                        .points()
+                        .filter(|&p| !walls[p] && enemies.iter().any(|&j| adjacent(p, units[j].0)))
+                        .filter_map(|p| from_unit[p].map(|d| (d, p)))
+                        .min();
+                    if let Some((_, target)) = target {
+                        let to_target = distances(&walls, &units, target);
+                        let step = walls
+                            .neighbours4(pos)
+                            .filter_map(|n| to_target[n].map(|d| (d, n)))
+                            .min()
+                            .unwrap();
+                        units[i].0 = step.1;
+                    }
+                }
+
+                let pos = units[i].0;
+                let victim = enemies
+                    .iter()
+                    .filter(|&&j| adjacent(pos, units[j].0))
+                    .min_by_key(|&&j| (units[j].2, units[j].0));
+                if let Some(&j) = victim {
+                    units[j].2 -= if elf { elf_attack } else { ATTACK_POWER };
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    #[test]
+    fn test_random_caves() {
+        generators::check(30, |rng| {
+            let map = generators::day15(rng);
+            let cave = input_gen(&map).unwrap();
+            assert_eq!(solve_part1(&cave), combat(&map, ATTACK_POWER).0);
+
+            let outcome = (4..)
+                .map(|attack| combat(&map, attack))
+                .find(|&(_, survived)| survived)
+                .unwrap()
+                .0;
+            assert_eq!(solve_part2(&cave), outcome);
+        });
     }
 }
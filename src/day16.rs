@@ -1,134 +1,104 @@
+use crate::error::{self, Line, ParseError};
+use crate::input;
 use regex::Regex;
 use std::collections::{HashMap, HashSet};
-use std::num::ParseIntError;
-use std::str::FromStr;
-
-fn input_gen(input: &str) -> (u32, HashMap<u32, OPCODE>, &str) {
-    let mut parts = input.split("\n\n\n");
-    let part1 = parts.next().unwrap();
-    let (part1_answer, op_map) = run_samples(
-        part1
-            .split("\n\n")
-            .map(|s| s.trim().parse::<Sample>().unwrap())
-            .collect(),
-    );
-    (part1_answer, op_map, parts.next().unwrap())
+
+const DAY: u8 = 16;
+const INSTRUCTION: &str = "an instruction like `9 0 1 2`";
+
+lazy_static! {
+    static ref before_re: Regex =
+        Regex::new(r"^Before:\s*\[(?P<r0>\d+),\s*(?P<r1>\d+),\s*(?P<r2>\d+),\s*(?P<r3>\d+)\]\s*$")
+            .unwrap();
+    static ref after_re: Regex =
+        Regex::new(r"^After:\s*\[(?P<r0>\d+),\s*(?P<r1>\d+),\s*(?P<r2>\d+),\s*(?P<r3>\d+)\]\s*$")
+            .unwrap();
+    static ref instruction_re: Regex =
+        Regex::new(r"^\s*(?P<r0>\d+)\s+(?P<r1>\d+)\s+(?P<r2>\d+)\s+(?P<r3>\d+)\s*$").unwrap();
+}
+
+// The puzzle input, samples of instructions being run followed by the test program
+pub struct Manual {
+    samples: Vec<Sample>,
+    program: Vec<[usize; 4]>,
+}
+
+#[aoc_generator(day16)]
+pub fn input_gen(input: &str) -> Result<Manual, ParseError> {
+    let input = &input::normalize(input);
+    let mut lines = error::lines(DAY, input)
+        .filter(|l| !l.text().trim().is_empty())
+        .peekable();
+    let mut samples = Vec::new();
+    while let Some(before) = lines.next_if(|l| l.text().starts_with("Before:")) {
+        let missing = |section| ParseError::missing(DAY, before.number() + 1, section);
+        let instruction = lines.next().ok_or_else(|| missing("sample instruction"))?;
+        let after = lines
+            .next()
+            .ok_or_else(|| missing("sample `After:` registers"))?;
+        let [opcode, input_a, input_b, output] =
+            parse_registers(&instruction, &instruction_re, INSTRUCTION)?;
+        samples.push(Sample {
+            before: parse_registers(&before, &before_re, "`Before: [0, 1, 2, 3]`")?,
+            after: parse_registers(&after, &after_re, "`After:  [0, 1, 2, 3]`")?,
+            input_a,
+            input_b,
+            output,
+            opcode: opcode as u32,
+        });
+    }
+
+    // Part 2 works the opcodes out from the samples
+    if samples.is_empty() {
+        let line = lines.peek().map_or(1, |l| l.number());
+        return Err(ParseError::missing(DAY, line, "samples"));
+    }
+    let program = lines
+        .map(|l| parse_registers(&l, &instruction_re, INSTRUCTION))
+        .collect::<Result<_, _>>()?;
+
+    Ok(Manual { samples, program })
+}
+
+fn parse_registers(
+    line: &Line,
+    re: &Regex,
+    expected: &'static str,
+) -> Result<[usize; 4], ParseError> {
+    let caps = re
+        .captures(line.text())
+        .ok_or_else(|| line.malformed(expected))?;
+    let field = |name| line.parse(caps.name(name).unwrap().as_str());
+    Ok([field("r0")?, field("r1")?, field("r2")?, field("r3")?])
 }
 
 #[aoc(day16, part1)]
-fn solve_part1(input: &str) -> u32 {
-    input_gen(input).0
+pub fn solve_part1(input: &Manual) -> u32 {
+    run_samples(&input.samples).0
 }
 
+// `None` if the samples don't pin down every opcode the program uses
 #[aoc(day16, part2)]
-fn solve_part2(input: &str) -> usize {
-    let (_, op_map, part2_input) = input_gen(input);
-    let mut model = Model::new(op_map);
-    for line in part2_input.trim().lines() {
-        let mut instruction: Vec<_> = line
-            .split_whitespace()
-            .map(|s| s.parse::<usize>().unwrap())
-            .collect();
-        model.execute(&instruction);
+pub fn solve_part2(input: &Manual) -> Option<usize> {
+    let (_, candidates) = run_samples(&input.samples);
+    let mut model = Model::new(final_opcode_map(candidates));
+    for instruction in &input.program {
+        model.execute(instruction)?;
     }
-    println!("registers: {:?}", &model.registers);
-    model.registers[0]
+    Some(model.registers[0])
 }
 
-fn run_samples(samples: Vec<Sample>) -> (u32, HashMap<u32, OPCODE>) {
-    use self::OPCODE::*;
+fn run_samples(samples: &[Sample]) -> (u32, HashMap<u32, HashSet<Opcode>>) {
     let mut three_or_more = 0;
-    let mut op_map: HashMap<u32, HashSet<OPCODE>> = HashMap::new();
-    for s in &samples {
-        let mut possible_op = 0;
-        let mut result = s.after[s.output];
-        let mut opcode_candidates = HashSet::new();
-        // check addr
-        if s.before[s.input_a] + s.before[s.input_b] == result {
-            opcode_candidates.insert(Addr);
-            possible_op += 1;
-        }
-        // check addi
-        if s.before[s.input_a] + s.input_b == result {
-            opcode_candidates.insert(Addi);
-            possible_op += 1;
-        }
-        // check mulr
-        if s.before[s.input_a] * s.before[s.input_b] == result {
-            opcode_candidates.insert(Mulr);
-            possible_op += 1;
-        }
-        // check muli
-        if s.before[s.input_a] * s.input_b == result {
-            opcode_candidates.insert(Muli);
-            possible_op += 1;
-        }
-        // check banr
-        if s.before[s.input_a] & s.before[s.input_b] == result {
-            opcode_candidates.insert(Banr);
-            possible_op += 1;
-        }
-        // check bani
-        if s.before[s.input_a] & s.input_b == result {
-            opcode_candidates.insert(Bani);
-            possible_op += 1;
-        }
-        // check borr
-        if s.before[s.input_a] | s.before[s.input_b] == result {
-            opcode_candidates.insert(Borr);
-            possible_op += 1;
-        }
-        // check bori
-        if s.before[s.input_a] | s.input_b == result {
-            opcode_candidates.insert(Bori);
-            possible_op += 1;
-        }
-        // check setr
-        if s.before[s.input_a] == result {
-            opcode_candidates.insert(Setr);
-            possible_op += 1;
-        }
-        // check seti
-        if s.input_a == result {
-            opcode_candidates.insert(Seti);
-            possible_op += 1;
-        }
-
-        if result == 0 || result == 1 {
-            // check gtir
-            if (s.input_a > s.before[s.input_b]) as usize == result {
-                opcode_candidates.insert(Gtir);
-                possible_op += 1;
-            }
-            // check gtri
-            if (s.before[s.input_a] > s.input_b) as usize == result {
-                opcode_candidates.insert(Gtri);
-                possible_op += 1;
-            }
-            // check gtrr
-            if (s.before[s.input_a] > s.before[s.input_b]) as usize == result {
-                opcode_candidates.insert(Gtrr);
-                possible_op += 1;
-            }
-
-            // check eqir
-            if (s.input_a == s.before[s.input_b]) as usize == result {
-                opcode_candidates.insert(Eqir);
-                possible_op += 1;
-            }
-            // check eqri
-            if (s.before[s.input_a] == s.input_b) as usize == result {
-                opcode_candidates.insert(Eqri);
-                possible_op += 1;
-            }
-            // check eqrr
-            if (s.before[s.input_a] == s.before[s.input_b]) as usize == result {
-                opcode_candidates.insert(Eqrr);
-                possible_op += 1;
-            }
-        }
+    let mut op_map: HashMap<u32, HashSet<Opcode>> = HashMap::new();
+    for s in samples {
+        let opcode_candidates: HashSet<Opcode> = Opcode::ALL
+            .iter()
+            .cloned()
+            .filter(|op| op.apply(s.before, s.input_a, s.input_b, s.output) == Some(s.after))
+            .collect();
 
-        if possible_op >= 3 {
+        if opcode_candidates.len() >= 3 {
             three_or_more += 1;
         }
 
@@ -142,22 +112,27 @@ fn run_samples(samples: Vec<Sample>) -> (u32, HashMap<u32, OPCODE>) {
         }
     }
 
-    (three_or_more, final_opcode_map(op_map))
+    (three_or_more, op_map)
 }
 
-fn final_opcode_map(mut m: HashMap<u32, HashSet<OPCODE>>) -> HashMap<u32, OPCODE> {
+// Stops early, with some opcodes left out, once the samples can't narrow
+// them down any further
+fn final_opcode_map(mut m: HashMap<u32, HashSet<Opcode>>) -> HashMap<u32, Opcode> {
     let mut result = HashMap::new();
     while result.len() < 16 {
         let mut removed = Vec::new();
         for (opcode, names) in &m {
             if names.len() == 1 {
-                let name = names.iter().next().unwrap().clone();
-                result.insert(*opcode, name.clone());
+                let name = *names.iter().next().unwrap();
+                result.insert(*opcode, name);
                 removed.push(name);
             }
         }
+        if removed.is_empty() {
+            break;
+        }
 
-        for (opcode, names) in &mut m {
+        for names in m.values_mut() {
             for r in &removed {
                 names.remove(r);
             }
@@ -168,7 +143,7 @@ fn final_opcode_map(mut m: HashMap<u32, HashSet<OPCODE>>) -> HashMap<u32, OPCODE
 }
 
 #[derive(Hash, Eq, PartialEq, Debug, Copy, Clone)]
-enum OPCODE {
+enum Opcode {
     Addr,
     Addi,
     Mulr,
@@ -187,77 +162,75 @@ enum OPCODE {
     Eqrr,
 }
 
+impl Opcode {
+    const ALL: [Opcode; 16] = [
+        Opcode::Addr,
+        Opcode::Addi,
+        Opcode::Mulr,
+        Opcode::Muli,
+        Opcode::Banr,
+        Opcode::Bani,
+        Opcode::Bori,
+        Opcode::Borr,
+        Opcode::Setr,
+        Opcode::Seti,
+        Opcode::Gtir,
+        Opcode::Gtri,
+        Opcode::Gtrr,
+        Opcode::Eqir,
+        Opcode::Eqri,
+        Opcode::Eqrr,
+    ];
+
+    // The registers after running the instruction, `None` if it names a
+    // register past 3 or the result overflows
+    fn apply(self, registers: [usize; 4], a: usize, b: usize, c: usize) -> Option<[usize; 4]> {
+        use self::Opcode::*;
+        let reg = |r: usize| registers.get(r).cloned();
+        let value = match self {
+            Addr => reg(a)?.checked_add(reg(b)?)?,
+            Addi => reg(a)?.checked_add(b)?,
+            Mulr => reg(a)?.checked_mul(reg(b)?)?,
+            Muli => reg(a)?.checked_mul(b)?,
+            Banr => reg(a)? & reg(b)?,
+            Bani => reg(a)? & b,
+            Borr => reg(a)? | reg(b)?,
+            Bori => reg(a)? | b,
+            Setr => reg(a)?,
+            Seti => a,
+            Gtir => (a > reg(b)?) as usize,
+            Gtri => (reg(a)? > b) as usize,
+            Gtrr => (reg(a)? > reg(b)?) as usize,
+            Eqir => (a == reg(b)?) as usize,
+            Eqri => (reg(a)? == b) as usize,
+            Eqrr => (reg(a)? == reg(b)?) as usize,
+        };
+        let mut registers = registers;
+        *registers.get_mut(c)? = value;
+        Some(registers)
+    }
+}
+
 struct Model {
-    op_map: HashMap<u32, OPCODE>,
+    op_map: HashMap<u32, Opcode>,
     registers: [usize; 4],
 }
 
 impl Model {
-    fn new(op_map: HashMap<u32, OPCODE>) -> Self {
+    fn new(op_map: HashMap<u32, Opcode>) -> Self {
         Model {
             op_map,
             registers: [0, 0, 0, 0],
         }
     }
 
-    fn execute(&mut self, instruction: &[usize]) {
-        use self::OPCODE::*;
-        let (opcode, a, b, c) = (
-            instruction[0] as u32,
-            instruction[1],
-            instruction[2],
-            instruction[3],
-        );
-        match self.op_map[&opcode] {
-            Addr => {
-                self.registers[c] = self.registers[a] + self.registers[b];
-            }
-            Addi => {
-                self.registers[c] = self.registers[a] + b;
-            }
-            Mulr => {
-                self.registers[c] = self.registers[a] * self.registers[b];
-            }
-            Muli => {
-                self.registers[c] = self.registers[a] * b;
-            }
-            Banr => {
-                self.registers[c] = self.registers[a] & self.registers[b];
-            }
-            Bani => {
-                self.registers[c] = self.registers[a] & b;
-            }
-            Borr => {
-                self.registers[c] = self.registers[a] | self.registers[b];
-            }
-            Bori => {
-                self.registers[c] = self.registers[a] | b;
-            }
-            Setr => {
-                self.registers[c] = self.registers[a];
-            }
-            Seti => {
-                self.registers[c] = a;
-            }
-            Gtir => {
-                self.registers[c] = (a > self.registers[b]) as usize;
-            }
-            Gtri => {
-                self.registers[c] = (self.registers[a] > b) as usize;
-            }
-            Gtrr => {
-                self.registers[c] = (self.registers[a] > self.registers[b]) as usize;
-            }
-            Eqir => {
-                self.registers[c] = (a == self.registers[b]) as usize;
-            }
-            Eqri => {
-                self.registers[c] = (self.registers[a] == b) as usize;
-            }
-            Eqrr => {
-                self.registers[c] = (self.registers[a] == self.registers[b]) as usize;
-            }
-        }
+    // `None` for an opcode that isn't in the map, or an instruction
+    // `Opcode::apply` can't run
+    fn execute(&mut self, instruction: &[usize; 4]) -> Option<()> {
+        let [opcode, a, b, c] = *instruction;
+        let op = *self.op_map.get(&(opcode as u32))?;
+        self.registers = op.apply(self.registers, a, b, c)?;
+        Some(())
     }
 }
 
@@ -271,41 +244,80 @@ struct Sample {
     opcode: u32,
 }
 
-impl FromStr for Sample {
-    type Err = ParseIntError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        lazy_static! {
-            static ref regexep: Regex = Regex::new(
-                r"(?x)
-                (?-x)Before: \[(?P<b0>\d), (?P<b1>\d), (?P<b2>\d), (?P<b3>\d)\](?x)\n
-                (?-x)(?P<OP>\d+) (?P<A>\d) (?P<B>\d) (?P<C>\d)(?x)\n
-                (?-x)After:  \[(?P<a0>\d), (?P<a1>\d), (?P<a2>\d), (?P<a3>\d)\]"
-            )
-            .unwrap();
-        }
-        assert!(regexep.is_match(s), "regex does not match string");
-        let caps = regexep.captures(s).unwrap();
-        let before = [
-            caps["b0"].parse()?,
-            caps["b1"].parse()?,
-            caps["b2"].parse()?,
-            caps["b3"].parse()?,
-        ];
-
-        let after = [
-            caps["a0"].parse()?,
-            caps["a1"].parse()?,
-            caps["a2"].parse()?,
-            caps["a3"].parse()?,
-        ];
-
-        Ok(Sample {
-            before,
-            after,
-            opcode: caps["OP"].parse()?,
-            input_a: caps["A"].parse()?,
-            input_b: caps["B"].parse()?,
-            output: caps["C"].parse()?,
-        })
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generators;
+
+    const SAMPLE: &str = "Before: [3, 2, 1, 1]\n9 2 1 2\nAfter:  [3, 2, 2, 1]\n";
+
+    #[test]
+    fn test_part1() {
+        let input = input_gen(SAMPLE).unwrap();
+        assert_eq!(input.samples.len(), 1);
+        assert_eq!(solve_part1(&input), 1);
+        // One sample can't tell which of three instructions 9 is
+        let input = input_gen(&format!("{}\n\n\n9 0 0 0\n", SAMPLE)).unwrap();
+        assert_eq!(solve_part2(&input), None);
+    }
+
+    #[test]
+    fn test_bad_input() {
+        let input = format!("{}\nBefore: [3, 2, 1, 1]\n9 2 1\n", SAMPLE);
+        let err = input_gen(&input).map(|_| ()).unwrap_err();
+        assert_eq!((err.day, err.line), (16, 6));
+
+        let input = format!("{}\n\n\n9 2 1 2\n9 2 x 2\n", SAMPLE);
+        let err = input_gen(&input).map(|_| ()).unwrap_err();
+        assert_eq!((err.line, err.column), (8, 1));
+
+        let err = input_gen("Before: [3, 2, 1, 1]\n").map(|_| ()).unwrap_err();
+        assert_eq!(
+            err.kind,
+            crate::error::ErrorKind::Missing("sample instruction")
+        );
+
+        let err = input_gen("").map(|_| ()).unwrap_err();
+        assert_eq!(err.kind, crate::error::ErrorKind::Missing("samples"));
+        let err = input_gen("\n\n9 2 1 2\n").map(|_| ()).unwrap_err();
+        assert_eq!(
+            (err.line, err.kind),
+            (3, crate::error::ErrorKind::Missing("samples"))
+        );
+    }
+
+    // Register numbers past 3 used to index out of bounds
+    #[test]
+    fn test_bad_registers() {
+        assert_eq!(Opcode::Addr.apply([1, 2, 3, 4], 7, 0, 1), None);
+        assert_eq!(
+            Opcode::Seti.apply([1, 2, 3, 4], 7, 0, 1),
+            Some([1, 7, 3, 4])
+        );
+        assert_eq!(Opcode::Seti.apply([1, 2, 3, 4], 7, 0, 4), None);
+        assert_eq!(Opcode::Muli.apply([usize::MAX, 0, 0, 0], 0, 2, 0), None);
+
+        let input = "Before: [3, 2, 1, 1]\n9 7 1 5\nAfter:  [3, 2, 2, 1]\n\n\n9 7 0 1\n";
+        assert_eq!(solve_part1(&input_gen(input).unwrap()), 0);
+        let mut model = Model::new(vec![(9, Opcode::Addr)].into_iter().collect());
+        assert_eq!(model.execute(&[9, 7, 0, 1]), None);
+        assert_eq!(model.execute(&[9, 0, 0, 1]), Some(()));
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        generators::check(50, |rng| {
+            let (input, register) = generators::day16(rng);
+            let manual = input_gen(&input).unwrap();
+            let three_or_more = manual.samples.iter().filter(|s| {
+                let [a, b, c] = [s.input_a, s.input_b, s.output];
+                (0..16)
+                    .filter(|&op| generators::execute(op, a, b, c, s.before) == s.after)
+                    .count()
+                    >= 3
+            });
+            assert_eq!(solve_part1(&manual) as usize, three_or_more.count());
+            assert_eq!(solve_part2(&manual), Some(register));
+        });
     }
 }
@@ -1,29 +1,66 @@
+use crate::error::{self, Line, ParseError};
+use crate::grid::{Grid, Point};
+use crate::input;
+use crate::render::{Frames, Image, RenderError, Rgb};
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::{
-    char::ParseCharError,
     cmp::{max, min},
-    convert::From,
+    fmt::{self, Display},
     str::FromStr,
 };
 
+const DAY: u8 = 17;
 const WATER_SPRING: Coord = Coord { x: 500, y: 0 };
+// The grid starts a column left of x=0, for water spilling off clay there
+const PADDING: usize = 1;
+
+type Coord = Point;
+
+#[aoc_generator(day17)]
+pub fn input_gen(input: &str) -> Result<Reservoir, ParseError> {
+    input::normalize(input).parse()
+}
 
 #[aoc(day17, part1)]
-fn solve_part1(input: &str) -> usize {
-    let mut r: Reservoir = input.parse().unwrap();
-    r.run(WATER_SPRING);
-    r.count()
+pub fn solve_part1(input: &Reservoir) -> usize {
+    settle(input).count()
 }
 
 #[aoc(day17, part2)]
-fn solve_part2(input: &str) -> usize {
-    let mut r: Reservoir = input.parse().unwrap();
-    r.run(WATER_SPRING);
-    r.rest_count()
+pub fn solve_part2(input: &Reservoir) -> usize {
+    settle(input).rest_count()
+}
+
+// The ground once the water has settled, left of the clay cropped off
+pub fn render(input: &Reservoir, frames: &mut Frames) -> Result<(), RenderError> {
+    let r = settle(input);
+    let tiles = &r.tiles;
+    let left = tiles
+        .iter()
+        .filter(|(_, s)| **s == State::Clay)
+        .map(|(c, _)| c.x)
+        .min()
+        .unwrap_or(1)
+        .saturating_sub(1);
+    let ground = Grid::from_fn(tiles.width() - left, tiles.height(), |c: Point| {
+        tiles[Point::new(c.x + left, c.y)].clone()
+    });
+    frames.push(Image::from_grid(&ground, |s| match s {
+        State::Sand => Rgb(230, 210, 160),
+        State::Clay => Rgb(120, 70, 30),
+        State::WetSand => Rgb(150, 200, 240),
+        State::RestingWater => Rgb(30, 80, 200),
+    }))
+}
+
+fn settle(input: &Reservoir) -> Reservoir {
+    let mut r = input.clone();
+    r.run(Point::new(WATER_SPRING.x + PADDING, WATER_SPRING.y));
+    r
 }
 
-#[derive(Hash, Eq, PartialEq, Debug)]
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
 enum State {
     Sand,
     WetSand, // sand that water has passed through
@@ -31,21 +68,23 @@ enum State {
     RestingWater,
 }
 
-#[derive(Hash, Eq, PartialEq, Copy, Clone, Debug)]
-struct Coord {
-    x: usize,
-    y: usize,
-}
-
-impl From<(usize, usize)> for Coord {
-    fn from(t: (usize, usize)) -> Self {
-        Coord { x: t.0, y: t.1 }
+impl Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let c = match self {
+            State::Sand => '.',
+            State::WetSand => '|',
+            State::Clay => '#',
+            State::RestingWater => '~',
+        };
+        write!(f, "{}", c)
     }
 }
 
-struct Reservoir {
+// Anything past the right or bottom edge of `tiles` is sand
+#[derive(Clone)]
+pub struct Reservoir {
     visited: HashSet<Coord>,
-    tiles: HashMap<Coord, State>,
+    tiles: Grid<State>,
     min_y: usize,
     max_y: usize,
 }
@@ -53,17 +92,13 @@ struct Reservoir {
 impl Reservoir {
     // water_start is a source of water like a water spring or the edge from an overflow
     fn run(&mut self, water_start: Coord) {
-        // repeatedly drop water from the current position making sure to only scan 
+        // repeatedly drop water from the current position making sure to only scan
         // new tiles and tiles in range
-        loop {
-            if let Some(mut c) = self.fall(water_start) {
-                if self.visited.contains(&c) {
-                    break;
-                }
-                self.scan(c);
-            } else {
+        while let Some(c) = self.fall(water_start) {
+            if self.visited.contains(&c) {
                 break;
             }
+            self.scan(c);
         }
     }
 
@@ -75,13 +110,12 @@ impl Reservoir {
             // no spill on either side means water can fill this area
             (false, false) => {
                 for x in flow_left.x..=flow_right.x {
-                    self.tiles
-                        .insert((x, flow_left.y).into(), State::RestingWater);
+                    self.tiles[Point::new(x, flow_left.y)] = State::RestingWater;
                 }
             }
             (fall_left, fall_right) => {
                 for x in flow_left.x..=flow_right.x {
-                    self.tiles.insert((x, flow_left.y).into(), State::WetSand);
+                    self.tiles[Point::new(x, flow_left.y)] = State::WetSand;
                 }
                 if fall_left {
                     self.run(flow_left);
@@ -98,8 +132,8 @@ impl Reservoir {
         let mut curr_water = water_start;
         // pour water downwards until we reach clay or resting water
         while {
-            match self.tiles.get(&(curr_water.x, curr_water.y + 1).into()) {
-                None | Some(&State::WetSand) | Some(&State::Sand) => true,
+            match self.tiles.get(Point::new(curr_water.x, curr_water.y + 1)) {
+                None | Some(&State::Sand) | Some(&State::WetSand) => true,
                 Some(&State::Clay) | Some(&State::RestingWater) => false,
             }
         } {
@@ -108,8 +142,7 @@ impl Reservoir {
             if curr_water.y > self.max_y {
                 return None;
             }
-            self.tiles
-                .insert((curr_water.x, curr_water.y).into(), State::WetSand);
+            self.tiles[curr_water] = State::WetSand;
         }
 
         Some(curr_water)
@@ -118,7 +151,7 @@ impl Reservoir {
     fn flow_left(&mut self, mut water_start: Coord) -> (Coord, bool) {
         let mut spill = false;
         while {
-            match self.tiles.get(&(water_start.x, water_start.y + 1).into()) {
+            match self.tiles.get(Point::new(water_start.x, water_start.y + 1)) {
                 Some(State::Clay) | Some(State::RestingWater) => true,
                 _ => {
                     spill = true;
@@ -126,7 +159,8 @@ impl Reservoir {
                 }
             }
         } {
-            match self.tiles.get(&(water_start.x - 1, water_start.y).into()) {
+            // Nothing's below the padding column, so it's never stepped past
+            match self.tiles.get(Point::new(water_start.x - 1, water_start.y)) {
                 Some(State::Clay) => break,
                 _ => water_start.x -= 1,
             }
@@ -138,7 +172,7 @@ impl Reservoir {
     fn flow_right(&mut self, mut water_start: Coord) -> (Coord, bool) {
         let mut spill = false;
         while {
-            match self.tiles.get(&(water_start.x, water_start.y + 1).into()) {
+            match self.tiles.get(Point::new(water_start.x, water_start.y + 1)) {
                 Some(State::Clay) | Some(State::RestingWater) => true,
                 _ => {
                     spill = true;
@@ -146,7 +180,7 @@ impl Reservoir {
                 }
             }
         } {
-            match self.tiles.get(&(water_start.x + 1, water_start.y).into()) {
+            match self.tiles.get(Point::new(water_start.x + 1, water_start.y)) {
                 Some(State::Clay) => break,
                 _ => water_start.x += 1,
             }
@@ -157,8 +191,8 @@ impl Reservoir {
 
     fn count(&self) -> usize {
         let mut tiles_water_reached = 0;
-        for (c, state) in &self.tiles {
-            if c.y < self.min_y || c.y > self.max_y {
+        for (c, state) in self.tiles.iter() {
+            if c.y < self.min_y {
                 continue;
             }
             match state {
@@ -172,13 +206,12 @@ impl Reservoir {
 
     fn rest_count(&self) -> usize {
         let mut resting_water = 0;
-        for (c, state) in &self.tiles {
-            if c.y < self.min_y || c.y > self.max_y {
+        for (c, state) in self.tiles.iter() {
+            if c.y < self.min_y {
                 continue;
             }
-            match state {
-                State::RestingWater => resting_water += 1,
-                _ => (),
+            if let State::RestingWater = state {
+                resting_water += 1;
             }
         }
 
@@ -192,50 +225,41 @@ lazy_static! {
 }
 
 impl FromStr for Reservoir {
-    type Err = ParseCharError;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut tiles = HashMap::new();
-        let (mut min_y, mut max_y) = (std::usize::MAX, 0);
-        for line in s.trim().lines() {
-            let line = line.trim();
-            if x_coords.is_match(line) {
-                let caps = x_coords.captures(line).unwrap();
-                let x_val: usize = caps["x"].parse().unwrap();
-                if caps["y"].contains("..") {
-                    let mut y_range = caps["y"].split("..").map(|n| n.parse::<usize>().unwrap());
-                    let y_start = y_range.next().unwrap();
-                    let y_end = y_range.next().unwrap();
-                    for n in y_start..=y_end {
-                        min_y = min(min_y, n);
-                        max_y = max(max_y, n);
-                        tiles.insert((x_val, n).into(), State::Clay);
-                    }
-                } else {
-                    let y_val: usize = caps["y"].parse().unwrap();
-                    min_y = min(min_y, y_val);
-                    max_y = max(max_y, y_val);
-                    tiles.insert((x_val, y_val).into(), State::Clay);
+        let mut clay = Vec::new();
+        let (mut min_y, mut max_y, mut max_x) = (usize::MAX, 0, WATER_SPRING.x);
+        for line in error::lines(DAY, s).filter(|l| !l.text().trim().is_empty()) {
+            if let Some(caps) = x_coords.captures(line.text()) {
+                let x_val: usize = line.parse(caps.name("x").unwrap().as_str())?;
+                let (y_start, y_end) = parse_range(&line, caps.name("y").unwrap().as_str())?;
+                max_x = max(max_x, x_val);
+                for n in y_start..=y_end {
+                    min_y = min(min_y, n);
+                    max_y = max(max_y, n);
+                    clay.push(Point::new(x_val + PADDING, n));
                 }
-            } else if y_coords.is_match(line) {
-                let caps = y_coords.captures(line).unwrap();
-                let y_val: usize = caps["y"].parse().unwrap();
+            } else if let Some(caps) = y_coords.captures(line.text()) {
+                let y_val: usize = line.parse(caps.name("y").unwrap().as_str())?;
                 min_y = min(min_y, y_val);
                 max_y = max(max_y, y_val);
-                if caps["x"].contains("..") {
-                    let mut x_range = caps["x"].split("..").map(|n| n.parse::<usize>().unwrap());
-                    let x_start = x_range.next().unwrap();
-                    let x_end = x_range.next().unwrap();
-                    for n in x_start..=x_end {
-                        tiles.insert((n, y_val).into(), State::Clay);
-                    }
-                } else {
-                    let x_val = caps["x"].parse().unwrap();
-                    tiles.insert((x_val, y_val).into(), State::Clay);
+                let (x_start, x_end) = parse_range(&line, caps.name("x").unwrap().as_str())?;
+                max_x = max(max_x, x_end);
+                for n in x_start..=x_end {
+                    clay.push(Point::new(n + PADDING, y_val));
                 }
+            } else {
+                return Err(line.malformed("a clay vein like `x=495, y=2..7`"));
             }
         }
 
+        // Leave a column right of the clay for water spilling over its edge
+        let mut tiles = Grid::new(max_x + PADDING + 2, max_y + 1, State::Sand);
+        for c in clay {
+            tiles[c] = State::Clay;
+        }
+
         Ok(Reservoir {
             tiles,
             min_y,
@@ -245,12 +269,167 @@ impl FromStr for Reservoir {
     }
 }
 
+impl Display for Reservoir {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.tiles)
+    }
+}
+
+// Either a single value `7` or an inclusive range `2..7`, never `7..2`
+fn parse_range(line: &Line, field: &str) -> Result<(usize, usize), ParseError> {
+    let mut bounds = field.split("..");
+    let start = line.parse(bounds.next().unwrap())?;
+    match bounds.next() {
+        Some(end) => match line.parse(end)? {
+            end if end < start => Err(line.malformed("a range from low to high like `2..7`")),
+            end => Ok((start, end)),
+        },
+        None => Ok((start, start)),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::ErrorKind;
+    use crate::generators;
+    use std::collections::HashMap;
+
     #[test]
     fn input() {
-        let count = solve_part1(include_str!("../input/tests/d17.txt"));
+        let count = solve_part1(&input_gen(include_str!("../input/tests/d17.txt")).unwrap());
         assert_eq!(count, 57);
     }
+
+    #[test]
+    fn bad_input() {
+        let err = input_gen("x=495, y=2..7\ny=7, x=495..501\nx=501 y=3..7")
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!((err.day, err.line), (17, 3));
+
+        // Reversed ranges used to read as empty veins
+        let err = input_gen("x=495, y=2..7\ny=7, x=501..495")
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!(
+            (err.line, err.kind),
+            (
+                2,
+                ErrorKind::Malformed("a range from low to high like `2..7`")
+            )
+        );
+        assert!(input_gen("x=495, y=7..2").is_err());
+        assert!(input_gen("x=495, y=7..7").is_ok());
+    }
+
+    // Water running off the left end of clay at x=0 used to step to x=-1
+    #[test]
+    fn left_edge() {
+        // The clay far off to the right only brings row 4 into the count
+        let reservoir = input_gen("y=5, x=0..500\nx=600, y=4").unwrap();
+        // Row 4 from x=-1 to 501, and down past both ends of the floor
+        assert_eq!(solve_part1(&reservoir), 503 + 2);
+        assert_eq!(solve_part2(&reservoir), 0);
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Ground {
+        Clay,
+        Flowing,
+        Still,
+    }
+
+    // Follows the water recursively, everything that isn't in `ground` is sand
+    struct Scan {
+        ground: HashMap<(usize, usize), Ground>,
+        max_y: usize,
+    }
+
+    impl Scan {
+        fn parse(input: &str) -> Scan {
+            let range = |s: &str| {
+                let mut bounds = s[2..].split("..").map(|n| n.parse::<usize>().unwrap());
+                let start = bounds.next().unwrap();
+                start..=bounds.next().unwrap_or(start)
+            };
+            let mut ground = HashMap::new();
+            for line in input.lines() {
+                let (a, b) = line.split_once(", ").unwrap();
+                for i in range(a) {
+                    for j in range(b) {
+                        let square = if a.starts_with('x') { (i, j) } else { (j, i) };
+                        ground.insert(square, Ground::Clay);
+                    }
+                }
+            }
+            let max_y = ground.keys().map(|s| s.1).max().unwrap();
+            Scan { ground, max_y }
+        }
+
+        // Whether water reaching `(x, y)` is held there rather than draining away
+        fn fill(&mut self, x: usize, y: usize) -> bool {
+            if y > self.max_y {
+                return false;
+            }
+            match self.ground.get(&(x, y)) {
+                Some(Ground::Clay) | Some(Ground::Still) => return true,
+                Some(Ground::Flowing) => return false,
+                None => (),
+            }
+            self.ground.insert((x, y), Ground::Flowing);
+            if !self.fill(x, y + 1) {
+                return false;
+            }
+
+            match (self.spread(x, y, false), self.spread(x, y, true)) {
+                (Some(left), Some(right)) => {
+                    for x in left..=right {
+                        self.ground.insert((x, y), Ground::Still);
+                    }
+                    true
+                }
+                _ => false,
+            }
+        }
+
+        // Spreads water sideways until it hits clay, returning how far it got,
+        // or `None` if it spills over an edge
+        fn spread(&mut self, mut x: usize, y: usize, right: bool) -> Option<usize> {
+            loop {
+                let next = if right { x + 1 } else { x - 1 };
+                if self.ground.get(&(next, y)) == Some(&Ground::Clay) {
+                    return Some(x);
+                }
+                x = next;
+                self.ground.insert((x, y), Ground::Flowing);
+                if !self.fill(x, y + 1) {
+                    return None;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_veins() {
+        generators::check(200, |rng| {
+            let input = generators::day17(rng);
+            let mut scan = Scan::parse(&input);
+            let min_y = scan.ground.keys().map(|s| s.1).min().unwrap();
+            scan.fill(WATER_SPRING.x, WATER_SPRING.y);
+            let water = |kinds: &[Ground]| {
+                scan.ground
+                    .iter()
+                    .filter(|(s, g)| s.1 >= min_y && kinds.contains(g))
+                    .count()
+            };
+
+            let reservoir = input_gen(&input).unwrap();
+            assert_eq!(
+                solve_part1(&reservoir),
+                water(&[Ground::Flowing, Ground::Still])
+            );
+            assert_eq!(solve_part2(&reservoir), water(&[Ground::Still]));
+        });
+    }
 }
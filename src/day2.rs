@@ -1,60 +1,288 @@
-use std::collections::HashMap;
+use crate::input;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
 
+// `None` if the checksum is too big to hold
 #[aoc(day2, part1)]
-pub fn solve_part1(input: &str) -> u32 {
-    let mut two_count = 0;
-    let mut three_count = 0;
-    for line in input.lines() {
-        let mut map = HashMap::new();
-        for c in line.chars() {
-            let entry = map.entry(c).or_insert(0);
-            *entry += 1;
-        }
-        let mut contains_two = false;
-        let mut contains_three = false;
-        for (_, o) in map.into_iter() {
-            if o == 2 {
-                contains_two = true;
-            } else if o == 3 {
-                contains_three = true;
+pub fn solve_part1(input: &str) -> Option<u128> {
+    let input = &input::normalize(input);
+    let ids: Vec<&str> = input.lines().collect();
+    checksum(&ids, &[2, 3]).value()
+}
+
+// Counts the IDs that have some letter exactly `n` times, for each `n` in
+// `multiplicities`, keeping what was counted so it can be checked. A
+// multiplicity given more than once is only counted once
+pub fn checksum(ids: &[&str], multiplicities: &[usize]) -> Checksum {
+    let histograms: Vec<Histogram> = ids.iter().map(|id| histogram(id)).collect();
+    let multiplicities: BTreeSet<usize> = multiplicities.iter().cloned().collect();
+    let contributors = multiplicities
+        .into_iter()
+        .map(|n| {
+            let ids = histograms
+                .iter()
+                .enumerate()
+                .filter(|(_, h)| h.values().any(|&times| times == n))
+                .map(|(i, _)| i)
+                .collect();
+            (n, ids)
+        })
+        .collect();
+
+    Checksum {
+        contributors,
+        histograms,
+    }
+}
+
+// How many times each letter (any char) appears in an ID
+pub type Histogram = BTreeMap<char, usize>;
+
+pub fn histogram(id: &str) -> Histogram {
+    let mut histogram = Histogram::new();
+    for c in id.chars() {
+        *histogram.entry(c).or_insert(0) += 1;
+    }
+    histogram
+}
+
+pub struct Checksum {
+    // Each multiplicity with the IDs (by index) counted for it
+    pub contributors: BTreeMap<usize, Vec<usize>>,
+    // One for every ID, in order
+    pub histograms: Vec<Histogram>,
+}
+
+impl Checksum {
+    pub fn count(&self, multiplicity: usize) -> usize {
+        self.contributors.get(&multiplicity).map_or(0, Vec::len)
+    }
+
+    // The product of the counts, `None` if it's too big to hold
+    pub fn value(&self) -> Option<u128> {
+        self.contributors
+            .values()
+            .try_fold(1u128, |product, ids| product.checked_mul(ids.len() as u128))
+    }
+}
+
+// The letters the two IDs that differ in just one place have in common
+#[aoc(day2, part2)]
+pub fn solve_part2(input: &str) -> Result<String, PairError> {
+    let input = &input::normalize(input);
+    let ids: Vec<&str> = input.lines().collect();
+    let pairs: Vec<_> = near_pairs(&ids, 1)
+        .into_iter()
+        .filter(|&(a, b)| ids[a] != ids[b])
+        .collect();
+    match pairs.as_slice() {
+        [] => Err(PairError::NoPair),
+        &[(a, b)] => Ok(ids[a]
+            .chars()
+            .zip(ids[b].chars())
+            .filter(|(x, y)| x == y)
+            .map(|(x, _)| x)
+            .collect()),
+        _ => Err(PairError::Several(pairs)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PairError {
+    NoPair,
+    // Indices of the IDs in each pair
+    Several(Vec<(usize, usize)>),
+}
+
+impl fmt::Display for PairError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PairError::NoPair => write!(f, "no two box IDs differ in exactly one place"),
+            PairError::Several(pairs) => {
+                let lines: Vec<_> = pairs
+                    .iter()
+                    .map(|(a, b)| format!("{} and {}", a + 1, b + 1))
+                    .collect();
+                write!(
+                    f,
+                    "{} pairs of box IDs differ in exactly one place, on lines {}",
+                    pairs.len(),
+                    lines.join(", ")
+                )
             }
         }
-        if contains_two {
-            two_count += 1;
+    }
+}
+
+impl Error for PairError {}
+
+// Every pair of IDs, by index and in order, that differ in at most `k`
+// places. IDs of different lengths are never near each other. Only IDs
+// that share a key are compared, see `keys`
+pub fn near_pairs(ids: &[&str], k: usize) -> Vec<(usize, usize)> {
+    let ids: Vec<Vec<char>> = ids.iter().map(|id| id.chars().collect()).collect();
+    let mut buckets: HashMap<(usize, usize, Vec<char>), Vec<usize>> = HashMap::new();
+    for (i, id) in ids.iter().enumerate() {
+        for (which, key) in keys(id, k) {
+            buckets.entry((id.len(), which, key)).or_default().push(i);
+        }
+    }
+
+    let mut pairs = BTreeSet::new();
+    for bucket in buckets.values() {
+        for (n, &a) in bucket.iter().enumerate() {
+            for &b in &bucket[n + 1..] {
+                if distance(&ids[a], &ids[b]) <= k {
+                    pairs.insert((a, b));
+                }
+            }
         }
+    }
+    pairs.into_iter().collect()
+}
 
-        if contains_three {
-            three_count += 1;
+// Two IDs within `k` of each other share at least one of these. For k = 1
+// they're the ID with each letter in turn left out, which two IDs only
+// share if that's the one letter they differ in, so nothing is compared
+// that doesn't match. Otherwise the ID is cut into k + 1 blocks, and k
+// differences can't touch all of them
+fn keys(id: &[char], k: usize) -> Vec<(usize, Vec<char>)> {
+    match k {
+        0 => vec![(0, id.to_vec())],
+        1 if !id.is_empty() => (0..id.len())
+            .map(|i| {
+                let mut key = id.to_vec();
+                key.remove(i);
+                (i, key)
+            })
+            .collect(),
+        _ => {
+            let blocks = k + 1;
+            let bound = |b| b * id.len() / blocks;
+            (0..blocks)
+                .map(|b| (b, id[bound(b)..bound(b + 1)].to_vec()))
+                .collect()
         }
     }
+}
 
-    return two_count * three_count;
+fn distance(a: &[char], b: &[char]) -> usize {
+    a.iter().zip(b).filter(|(x, y)| x != y).count()
 }
 
-#[aoc(day2, part2)]
-pub fn solve_part2(input: &str) -> String {
-    let mut candidate1 = "";
-    let mut candidate2 = "";
-    for l1 in input.lines() {
-        for l2 in input.lines() {
-            let mut diff_count = 0;
-            for (c1, c2) in l1.chars().zip(l2.chars()) {
-                if c1 != c2 {
-                    diff_count += 1;
-                }
-            }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generators;
+    use std::collections::HashSet;
 
-            if diff_count == 1 {
-                candidate1 = l1;
-                candidate2 = l2;
+    // Sorting an ID puts repeated letters next to each other
+    fn has_repeat(id: &str, times: usize) -> bool {
+        let mut letters: Vec<char> = id.chars().collect();
+        letters.sort_unstable();
+        let mut runs = Vec::new();
+        for (i, c) in letters.iter().enumerate() {
+            if i == 0 || letters[i - 1] != *c {
+                runs.push(0);
             }
+            *runs.last_mut().unwrap() += 1;
         }
+        runs.contains(&times)
     }
-    let mut res = String::new();
-    for (c1, c2) in candidate1.chars().zip(candidate2.chars()) {
-        if c1 == c2 {
-            res.push(c1);
+
+    // The pair of IDs are the same once their differing letter is dropped
+    fn common_letters(input: &str) -> String {
+        let len = input.lines().next().unwrap().len();
+        for i in 0..len {
+            let mut seen = HashSet::new();
+            for id in input.lines() {
+                let dropped = format!("{}{}", &id[..i], &id[i + 1..]);
+                if !seen.insert(dropped.clone()) {
+                    return dropped;
+                }
+            }
         }
+        panic!("no pair of IDs in\n{}", input)
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        generators::check(100, |rng| {
+            let input = generators::day2(rng);
+            let count = |times| input.lines().filter(|id| has_repeat(id, times)).count();
+            assert_eq!(solve_part1(&input), Some((count(2) * count(3)) as u128));
+            assert_eq!(solve_part2(&input).unwrap(), common_letters(&input));
+        });
+    }
+
+    #[test]
+    fn test_checksum() {
+        let ids = ["aäbäa", "äöö", "xyz", "ääää"];
+        let sum = checksum(&ids, &[2, 3, 4]);
+        assert_eq!(
+            sum.histograms[0],
+            [('a', 2), ('b', 1), ('ä', 2)].iter().cloned().collect()
+        );
+        assert_eq!(sum.contributors[&2], vec![0, 1]);
+        assert_eq!((sum.count(3), sum.count(4), sum.count(5)), (0, 1, 0));
+        assert_eq!(sum.value(), Some(0));
+        assert_eq!(checksum(&ids, &[1, 2]).value(), Some(6));
+        assert_eq!(checksum(&ids, &[]).value(), Some(1));
+
+        generators::check(100, |rng| {
+            let input = generators::day2(rng);
+            let ids: Vec<&str> = input.lines().collect();
+            let multiplicities: Vec<usize> = (0..rng.between(0, 4))
+                .map(|_| rng.between(1, 5) as usize)
+                .collect();
+            let sum = checksum(&ids, &multiplicities);
+            for &n in &multiplicities {
+                let expected: Vec<_> = (0..ids.len()).filter(|&i| has_repeat(ids[i], n)).collect();
+                assert_eq!(sum.contributors[&n], expected);
+            }
+            let distinct: HashSet<_> = multiplicities.iter().collect();
+            assert_eq!(sum.contributors.len(), distinct.len());
+        });
+    }
+
+    #[test]
+    fn test_part2() {
+        let input = "abcde\nfghij\nklmno\npqrst\nfguij\naxcye\nwvxyz\n";
+        assert_eq!(solve_part2(input).unwrap(), "fgij");
+        assert_eq!(solve_part2("abc\nabc\nxyz\n"), Err(PairError::NoPair));
+
+        let err = solve_part2("abc\nabd\nxbd\n").unwrap_err();
+        assert_eq!(err, PairError::Several(vec![(0, 1), (1, 2)]));
+        assert_eq!(
+            err.to_string(),
+            "2 pairs of box IDs differ in exactly one place, on lines 1 and 2, 2 and 3"
+        );
+    }
+
+    #[test]
+    fn test_near_pairs() {
+        generators::check(100, |rng| {
+            let k = rng.below(4);
+            let letters = *rng.pick(&[&['a', 'b'][..], &['ä', 'b', 'ç'][..]]);
+            let ids: Vec<String> = (0..rng.between(0, 40))
+                .map(|_| {
+                    let len = rng.between(0, 6) as usize;
+                    (0..len).map(|_| *rng.pick(letters)).collect()
+                })
+                .collect();
+            let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+
+            let mut expected = Vec::new();
+            for (a, x) in ids.iter().enumerate() {
+                for (b, y) in ids.iter().enumerate().skip(a + 1) {
+                    let (x, y): (Vec<_>, Vec<_>) = (x.chars().collect(), y.chars().collect());
+                    if x.len() == y.len() && distance(&x, &y) <= k {
+                        expected.push((a, b));
+                    }
+                }
+            }
+            assert_eq!(near_pairs(&ids, k), expected, "k = {}", k);
+        });
     }
-    return res;
 }
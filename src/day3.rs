@@ -1,126 +1,281 @@
+use crate::error::{self, ParseError};
+use crate::grid::{Grid, Point};
+use crate::input;
+use regex::Regex;
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+const DAY: u8 = 3;
+
+lazy_static! {
+    static ref regexp: Regex = Regex::new(
+        r"(?x)
+            ^\#(?P<id>[0-9]+) \s* @ \s*
+            (?P<left>[0-9]+),(?P<top>[0-9]+): \s*
+            (?P<width>[0-9]+)x(?P<height>[0-9]+)$
+        "
+    )
+    .unwrap();
+}
+
 #[derive(Debug)]
 pub struct Claim {
-    pub id: u16,
+    pub id: u64,
     pub left: u32,
     pub top: u32,
     pub width: u32,
     pub height: u32,
 }
 
+impl Claim {
+    pub fn right(&self) -> u64 {
+        u64::from(self.left) + u64::from(self.width)
+    }
+
+    pub fn bottom(&self) -> u64 {
+        u64::from(self.top) + u64::from(self.height)
+    }
+
+    // The square inches of fabric both claims want
+    pub fn overlap(&self, other: &Claim) -> u64 {
+        let width =
+            min(self.right(), other.right()).saturating_sub(u64::from(max(self.left, other.left)));
+        let height =
+            min(self.bottom(), other.bottom()).saturating_sub(u64::from(max(self.top, other.top)));
+        width * height
+    }
+}
+
+// The line the claim was read from
+impl Display for Claim {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{} @ {},{}: {}x{}",
+            self.id, self.left, self.top, self.width, self.height
+        )
+    }
+}
+
 #[aoc_generator(day3)]
-pub fn input_gen(input: &str) -> Vec<Claim> {
-    input
-        .lines()
+pub fn input_gen(input: &str) -> Result<Vec<Claim>, ParseError> {
+    let input = &input::normalize(input);
+    error::lines(DAY, input)
         .map(|line| {
-            let (first, last) = line.split_at(line.find('@').unwrap() + 1);
-            let id = first
-                .trim_matches(|c| c == '#' || c == '@')
-                .trim()
-                .parse()
-                .unwrap();
-            let parts = last.split(": ").collect::<Vec<&str>>();
-            let edges = parts[0]
-                .split(",")
-                .map(|s| s.trim().parse().unwrap())
-                .collect::<Vec<u32>>();
-            let dimensions = parts[1]
-                .split("x")
-                .map(|s| s.parse().unwrap())
-                .collect::<Vec<u32>>();
-            Claim {
-                id,
-                left: edges[0],
-                top: edges[1],
-                width: dimensions[0],
-                height: dimensions[1],
-            }
+            let caps = regexp
+                .captures(line.text())
+                .ok_or_else(|| line.malformed("a claim like `#1 @ 1,3: 4x4`"))?;
+            Ok(Claim {
+                id: line.parse(caps.name("id").unwrap().as_str())?,
+                left: line.parse(caps.name("left").unwrap().as_str())?,
+                top: line.parse(caps.name("top").unwrap().as_str())?,
+                width: line.parse(caps.name("width").unwrap().as_str())?,
+                height: line.parse(caps.name("height").unwrap().as_str())?,
+            })
         })
         .collect()
 }
 
+// Sweeps a line across the fabric from left to right, keeping how many
+// claims cover each stretch of it between the claims' top and bottom edges
 #[aoc(day3, part1)]
-pub fn solve_part1(input: &[Claim]) -> u32 {
-    let mut fabric = [[0i16; 1000]; 1000];
-    for c in input.iter() {
-        create_claim(c, &mut fabric);
-    }
-    let mut two_or_more_claims = 0;
-    for row in fabric.iter() {
-        for col in row.iter() {
-            if *col == -1 {
-                two_or_more_claims += 1;
+pub fn solve_part1(input: &[Claim]) -> u64 {
+    let mut edges: Vec<u64> = input
+        .iter()
+        .flat_map(|c| vec![u64::from(c.top), c.bottom()])
+        .collect();
+    edges.sort_unstable();
+    edges.dedup();
+
+    let mut events: Vec<(u64, i32, &Claim)> = input
+        .iter()
+        .flat_map(|c| vec![(u64::from(c.left), 1, c), (c.right(), -1, c)])
+        .collect();
+    events.sort_unstable_by_key(|&(x, change, _)| (x, change));
+
+    let mut covered = vec![0; edges.len().saturating_sub(1)];
+    // The length of the line covered by two or more claims
+    let mut overlapping = 0;
+    let (mut area, mut last_x) = (0, 0);
+    for (x, change, claim) in events {
+        area += (x - last_x) * overlapping;
+        last_x = x;
+
+        let edge = |y| edges.binary_search(&y).unwrap();
+        for stretch in edge(u64::from(claim.top))..edge(claim.bottom()) {
+            let length = edges[stretch + 1] - edges[stretch];
+            match (covered[stretch], change) {
+                (1, 1) => overlapping += length,
+                (2, -1) => overlapping -= length,
+                _ => (),
             }
+            covered[stretch] += change;
         }
     }
 
-    two_or_more_claims
+    area
 }
 
+// The last claim that doesn't overlap any other, `None` if they all do
 #[aoc(day3, part2)]
-pub fn solve_part2(input: &[Claim]) -> u16 {
-    let mut fabric = [[0i16; 1000]; 1000];
-    let mut id = 0;
-    for c in input.iter() {
-        create_claim(c, &mut fabric);
+pub fn solve_part2(input: &[Claim]) -> Option<u64> {
+    Overlaps::new(input).intact().last().map(|c| c.id)
+}
+
+// Which claims overlap which, and by how much
+pub struct Overlaps<'a> {
+    claims: &'a [Claim],
+    // The claims, by index, that each claim overlaps with the square inches they share
+    edges: Vec<Vec<(usize, u64)>>,
+    index: HashMap<u64, usize>,
+}
+
+impl<'a> Overlaps<'a> {
+    pub fn new(claims: &'a [Claim]) -> Self {
+        let mut edges = vec![Vec::new(); claims.len()];
+        for (a, b) in overlapping_pairs(claims) {
+            let shared = claims[a].overlap(&claims[b]);
+            edges[a].push((b, shared));
+            edges[b].push((a, shared));
+        }
+        for e in edges.iter_mut() {
+            e.sort_unstable();
+        }
+
+        let mut index = HashMap::new();
+        for (i, c) in claims.iter().enumerate().rev() {
+            index.insert(c.id, i);
+        }
+
+        Overlaps {
+            claims,
+            edges,
+            index,
+        }
     }
 
-    for c in input.iter() {
-        if check_claim(c, &mut fabric) {
-            id = c.id;
+    // The claims that overlap the claim with `id` (the first, if more than
+    // one has it) and by how much, in input order. `None` if there's no such claim
+    pub fn of(&self, id: u64) -> Option<Vec<(&'a Claim, u64)>> {
+        let edges = &self.edges[*self.index.get(&id)?];
+        Some(
+            edges
+                .iter()
+                .map(|&(i, shared)| (&self.claims[i], shared))
+                .collect(),
+        )
+    }
+
+    // The claims that don't overlap any other, in input order
+    pub fn intact(&self) -> Vec<&'a Claim> {
+        self.claims
+            .iter()
+            .zip(&self.edges)
+            .filter(|(_, e)| e.is_empty())
+            .map(|(c, _)| c)
+            .collect()
+    }
+
+    // Groups of claims joined by overlaps, directly or through other
+    // claims. Intact claims are groups of their own
+    pub fn components(&self) -> Vec<Vec<&'a Claim>> {
+        let mut seen = vec![false; self.claims.len()];
+        let mut components = Vec::new();
+        for start in 0..self.claims.len() {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut members = vec![start];
+            let mut next = 0;
+            while let Some(&claim) = members.get(next) {
+                next += 1;
+                for &(other, _) in &self.edges[claim] {
+                    if !seen[other] {
+                        seen[other] = true;
+                        members.push(other);
+                    }
+                }
+            }
+            members.sort_unstable();
+            components.push(members.into_iter().map(|i| &self.claims[i]).collect());
         }
+        components
     }
-    id
 }
 
-fn create_claim(claim: &Claim, fabric: &mut [[i16; 1000]; 1000]) {
-    for row in fabric
-        .iter_mut()
-        .skip(claim.top as usize)
-        .take(claim.height as usize)
-    {
-        for col in row
-            .iter_mut()
-            .skip(claim.left as usize)
-            .take(claim.width as usize)
-        {
-            if *col == 0 {
-                *col = claim.id as i16;
-            } else {
-                *col = -1;
+// The fabric from the top left corner to one past the furthest claim, as
+// the puzzle draws it: each claim's ID where it's the only claim, `X`
+// where claims overlap and `.` where there are none. Squares are as wide
+// as the longest ID. Only meant for small sets of claims
+pub fn draw(claims: &[Claim]) -> Vec<String> {
+    let width = claims.iter().map(Claim::right).max().unwrap_or(0) as usize + 1;
+    let height = claims.iter().map(Claim::bottom).max().unwrap_or(0) as usize + 1;
+    let mut fabric: Grid<Option<&Claim>> = Grid::new(width, height, None);
+    let mut overlapped = Grid::new(width, height, false);
+    for c in claims {
+        for y in u64::from(c.top)..c.bottom() {
+            for x in u64::from(c.left)..c.right() {
+                let p = Point::new(x as usize, y as usize);
+                overlapped[p] |= fabric[p].is_some();
+                fabric[p] = Some(c);
             }
         }
     }
+
+    let square = claims
+        .iter()
+        .map(|c| c.id.to_string().len())
+        .max()
+        .unwrap_or(1);
+    (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    let p = Point::new(x, y);
+                    match fabric[p] {
+                        _ if overlapped[p] => format!("{:>1$}", "X", square),
+                        Some(c) => format!("{:>1$}", c.id, square),
+                        None => format!("{:>1$}", ".", square),
+                    }
+                })
+                .collect()
+        })
+        .collect()
 }
 
-fn check_claim(claim: &Claim, fabric: &mut [[i16; 1000]; 1000]) -> bool {
-    let intact = true;
-    for row in fabric
-        .iter_mut()
-        .skip(claim.top as usize)
-        .take(claim.height as usize)
-    {
-        for col in row
-            .iter_mut()
-            .skip(claim.left as usize)
-            .take(claim.width as usize)
-        {
-            if *col != claim.id as i16 {
-                return false;
+// Every pair of claims, by index, that overlap. Going through them from
+// left to right, a claim is only compared with the ones that start
+// before it ends
+fn overlapping_pairs(claims: &[Claim]) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..claims.len()).collect();
+    order.sort_by_key(|&i| claims[i].left);
+
+    let mut pairs = Vec::new();
+    for (n, &a) in order.iter().enumerate() {
+        for &b in &order[n + 1..] {
+            if u64::from(claims[b].left) >= claims[a].right() {
+                break;
+            }
+            if claims[a].overlap(&claims[b]) > 0 {
+                pairs.push((min(a, b), max(a, b)));
             }
         }
     }
-
-    intact
+    pairs
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::generators;
+    use std::collections::{HashMap, HashSet};
 
     #[test]
     fn test_input() {
         let input = include_str!("../input/tests/d3.txt");
-        let claims = input_gen(input);
+        let claims = input_gen(input).unwrap();
         let result = solve_part1(&claims);
         assert_eq!(claims.len(), 3);
         assert_eq!(result, 4);
@@ -129,9 +284,149 @@ mod tests {
     #[test]
     fn test_input2() {
         let input = include_str!("../input/tests/d3.txt");
-        let claims = input_gen(input);
+        let claims = input_gen(input).unwrap();
         let result = solve_part2(&claims);
         assert_eq!(claims.len(), 3);
-        assert_eq!(result, 3);
+        assert_eq!(result, Some(3));
+
+        // Claim #0 is as good an answer as any other
+        let claims = input_gen("#0 @ 1,1: 2x2\n#1 @ 4,4: 1x1\n#2 @ 4,4: 2x2\n").unwrap();
+        assert_eq!(solve_part2(&claims), Some(0));
+        assert_eq!(solve_part2(&claims[1..]), None);
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let claims = input_gen(include_str!("../input/tests/d3.txt")).unwrap();
+        let overlaps = Overlaps::new(&claims);
+        let ids = |claims: Vec<&Claim>| claims.iter().map(|c| c.id).collect::<Vec<_>>();
+
+        let of_1: Vec<_> = overlaps
+            .of(1)
+            .unwrap()
+            .iter()
+            .map(|(c, n)| (c.id, *n))
+            .collect();
+        assert_eq!(of_1, vec![(2, 4)]);
+        assert!(overlaps.of(3).unwrap().is_empty());
+        assert!(overlaps.of(4).is_none());
+        assert_eq!(ids(overlaps.intact()), vec![3]);
+
+        let components: Vec<_> = overlaps.components().into_iter().map(ids).collect();
+        assert_eq!(components, vec![vec![1, 2], vec![3]]);
+    }
+
+    #[test]
+    fn test_draw() {
+        let claims = input_gen(include_str!("../input/tests/d3.txt")).unwrap();
+        let fabric = [
+            "........", "...2222.", "...2222.", ".11XX22.", ".11XX22.", ".111133.", ".111133.",
+            "........",
+        ];
+        assert_eq!(draw(&claims), fabric);
+
+        let claims = input_gen("#12 @ 0,0: 2x1\n#7 @ 1,0: 1x2\n").unwrap();
+        assert_eq!(draw(&claims), vec!["12 X .", " . 7 .", " . . ."]);
+        assert_eq!(draw(&[]), vec!["."]);
+    }
+
+    #[test]
+    fn test_bad_claim() {
+        let err = input_gen("#1 @ 1,3: 4x4\n#2 @ 3,1 4x4").unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err = input_gen("#99999999999999999999 @ 1,3: 4x4").unwrap_err();
+        assert_eq!((err.column, err.text.as_str()), (2, "99999999999999999999"));
+    }
+
+    #[test]
+    fn test_large_claims() {
+        let input = "#4000000000 @ 5000000,7000000: 3000x2000
+#70000 @ 5002000,7001000: 4000000x4000000
+#3 @ 4000000000,1: 5x5
+";
+        let claims = input_gen(input).unwrap();
+        assert_eq!(claims[0].overlap(&claims[1]), 1_000_000);
+        assert_eq!(solve_part1(&claims), 1_000_000);
+        assert_eq!(solve_part2(&claims), Some(3));
+    }
+
+    // How many claims cover each square inch
+    fn claimed(claims: &[Claim]) -> HashMap<(u32, u32), usize> {
+        let mut claimed = HashMap::new();
+        for c in claims {
+            for x in c.left..c.left + c.width {
+                for y in c.top..c.top + c.height {
+                    *claimed.entry((x, y)).or_insert(0) += 1;
+                }
+            }
+        }
+        claimed
+    }
+
+    fn claimed_by(c: &Claim) -> HashSet<(u32, u32)> {
+        let mut squares = HashSet::new();
+        for x in c.left..c.left + c.width {
+            for y in c.top..c.top + c.height {
+                squares.insert((x, y));
+            }
+        }
+        squares
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        generators::check(100, |rng| {
+            let input = generators::day3(rng);
+            let claims = input_gen(&input).unwrap();
+            let written: Vec<_> = claims.iter().map(Claim::to_string).collect();
+            assert_eq!(written.join("\n") + "\n", input);
+
+            let claimed = claimed(&claims);
+            let overlaps = claimed.values().filter(|&&n| n > 1).count();
+            assert_eq!(solve_part1(&claims) as usize, overlaps);
+
+            for (i, a) in claims.iter().enumerate() {
+                for b in &claims[i + 1..] {
+                    let shared = claimed_by(a).intersection(&claimed_by(b)).count();
+                    assert_eq!(a.overlap(b) as usize, shared);
+                }
+            }
+
+            let intact = claims.iter().rev().find(|c| {
+                (c.left..c.left + c.width)
+                    .all(|x| (c.top..c.top + c.height).all(|y| claimed[&(x, y)] == 1))
+            });
+            assert_eq!(solve_part2(&claims), intact.map(|c| c.id));
+
+            let overlaps = Overlaps::new(&claims);
+            let all_intact = claims.iter().filter(|c| {
+                (c.left..c.left + c.width)
+                    .all(|x| (c.top..c.top + c.height).all(|y| claimed[&(x, y)] == 1))
+            });
+            let ids = |claims: &[&Claim]| claims.iter().map(|c| c.id).collect::<Vec<_>>();
+            assert_eq!(
+                ids(&overlaps.intact()),
+                all_intact.map(|c| c.id).collect::<Vec<_>>()
+            );
+
+            // Claims in different groups never overlap, and within a group
+            // every claim overlaps another
+            let components = overlaps.components();
+            assert_eq!(components.iter().map(Vec::len).sum::<usize>(), claims.len());
+            for (i, group) in components.iter().enumerate() {
+                for c in group {
+                    let others = overlaps.of(c.id).unwrap();
+                    assert_eq!(others.is_empty(), group.len() == 1);
+                    for (other, shared) in others {
+                        assert!(group.iter().any(|g| g.id == other.id));
+                        assert_eq!(shared, c.overlap(other));
+                    }
+                    for other in components[i + 1..].iter().flatten() {
+                        assert_eq!(c.overlap(other), 0);
+                    }
+                }
+            }
+        });
     }
 }
@@ -1,11 +1,18 @@
+use crate::error::{self, ParseError};
+use crate::input;
 use regex::Regex;
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::fmt;
 use std::ops::Range;
 
+const DAY: u8 = 4;
+
 lazy_static! {
     static ref regexp: Regex = Regex::new(
         r"(?x)
-            \[[0-9]{4}
+            \[(?P<year>[0-9]{4,})
             -(?P<month>[0-9]{2})
             -(?P<day>[0-9]{2})
             \s+
@@ -22,12 +29,90 @@ lazy_static! {
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Record {
+    pub time: Timestamp,
+    pub action: Action,
+    // Where it was in the input, as records are sorted by time
+    pub line: usize,
+}
+
+// Fields are in order of significance, so timestamps sort by when they are
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    pub year: u32,
     pub month: u8,
     pub day: u8,
     pub hour: u8,
     pub minute: u8,
-    pub action: Action,
 }
+
+impl Timestamp {
+    // `None` unless it's a real date and time
+    pub fn new(year: u32, month: u8, day: u8, hour: u8, minute: u8) -> Option<Self> {
+        let leap =
+            year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400));
+        let days_in_month = match month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => 0,
+        };
+        if day == 0 || day > days_in_month || hour > 23 || minute > 59 {
+            return None;
+        }
+        Some(Timestamp {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+        })
+    }
+
+    // Minutes since 0000-03-01 00:00 (proleptic Gregorian), so that
+    // subtracting two gives the time between them whatever the date
+    pub fn minutes(&self) -> i64 {
+        let (month, day) = (i64::from(self.month), i64::from(self.day));
+        // Years are counted from March, which puts leap days at the end
+        let year = i64::from(self.year) - if month <= 2 { 1 } else { 0 };
+        let (era, year_of_era) = (year.div_euclid(400), year.rem_euclid(400));
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era;
+        (days * 24 + i64::from(self.hour)) * 60 + i64::from(self.minute)
+    }
+
+    // The other way round from `minutes`
+    pub fn from_minutes(minutes: i64) -> Self {
+        let days = minutes.div_euclid(MINUTES_IN_DAY);
+        let (era, day_of_era) = (days.div_euclid(146_097), days.rem_euclid(146_097));
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let month = (month_from_march + 2) % 12 + 1;
+        let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+        let minute_of_day = minutes.rem_euclid(MINUTES_IN_DAY);
+        Timestamp {
+            year: year as u32,
+            month: month as u8,
+            day: (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u8,
+            hour: (minute_of_day / 60) as u8,
+            minute: (minute_of_day % 60) as u8,
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute
+        )
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Action {
     ShiftStart(u32),
@@ -35,125 +120,715 @@ pub enum Action {
     Wake,
 }
 
-type SleepMap = HashMap<u32, Vec<Range<u32>>>;
+// Every nap each guard took, as `Timestamp::minutes`
+pub type SleepMap = HashMap<u32, Vec<Range<i64>>>;
+
+const MINUTES_IN_DAY: i64 = 24 * 60;
 
 #[aoc_generator(day4)]
-pub fn input_gen(input: &str) -> Vec<Record> {
-    let mut records: Vec<Record> = input
-        .lines()
-        .map(|l| {
-            let caps = regexp.captures(l).unwrap();
+pub fn input_gen(input: &str) -> Result<Vec<Record>, ParseError> {
+    let input = &input::normalize(input);
+    let mut records = error::lines(DAY, input)
+        .map(|line| {
+            let caps = regexp
+                .captures(line.text())
+                .ok_or_else(|| line.malformed("a record like `[1518-11-01 00:00] wakes up`"))?;
             let action = {
                 if caps["action"].contains("falls") {
                     Action::Sleep
                 } else if caps["action"].contains("wakes") {
                     Action::Wake
                 } else {
-                    let c = action_re.captures(&caps["action"]).unwrap();
-                    Action::ShiftStart(c["id"].parse().unwrap())
+                    let c = action_re
+                        .captures(caps.name("action").unwrap().as_str())
+                        .ok_or_else(|| line.malformed("a guard id in `Guard #10 begins shift`"))?;
+                    Action::ShiftStart(line.parse(c.name("id").unwrap().as_str())?)
                 }
             };
-            Record {
-                month: caps["month"].parse().unwrap(),
-                day: caps["day"].parse().unwrap(),
-                hour: caps["hour"].parse().unwrap(),
-                minute: caps["minute"].parse().unwrap(),
+            let field = |name| caps.name(name).unwrap().as_str();
+            let time = Timestamp::new(
+                line.parse(field("year"))?,
+                line.parse(field("month"))?,
+                line.parse(field("day"))?,
+                line.parse(field("hour"))?,
+                line.parse(field("minute"))?,
+            )
+            .ok_or_else(|| line.malformed("a real date and time like `[1518-11-01 00:00]`"))?;
+            Ok(Record {
+                time,
                 action,
-            }
+                line: line.number(),
+            })
         })
-        .collect();
+        .collect::<Result<Vec<_>, _>>()?;
     records.sort_unstable();
-    records
+    Ok(records)
+}
+
+// What to do with a log that doesn't add up, see `Problem`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Reject,
+    Repair,
+}
+
+// Something a guard can't have done. Repairing the log ignores the
+// record it's about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Problem {
+    // Falls asleep or wakes up before any shift has started
+    NoGuard,
+    // Falls asleep again without waking up
+    AlreadyAsleep,
+    // Wakes up without having fallen asleep
+    AlreadyAwake,
+    // Falls asleep and is still asleep when the next shift starts or the
+    // log ends. There's no telling how long for, so the nap is dropped
+    NeverWakes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inconsistency {
+    pub line: usize,
+    pub time: Timestamp,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let problem = match self.problem {
+            Problem::NoGuard => "nobody is on duty yet",
+            Problem::AlreadyAsleep => "falls asleep while already asleep",
+            Problem::AlreadyAwake => "wakes up without having fallen asleep",
+            Problem::NeverWakes => "falls asleep and never wakes up",
+        };
+        write!(f, "line {} [{}]: {}", self.line, self.time, problem)
+    }
+}
+
+// Every inconsistency in a log that was rejected, in time order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogError(pub Vec<Inconsistency>);
+
+impl fmt::Display for LogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let found: Vec<_> = self.0.iter().map(|i| i.to_string()).collect();
+        write!(f, "the guard log doesn't add up: {}", found.join("; "))
+    }
 }
 
-fn create_sleep_freq_map(input: &[Record]) -> SleepMap {
-    let (mut guard_on_duty, mut sleep_start) = (0, 0);
+impl Error for LogError {}
+
+pub fn create_sleep_freq_map(input: &[Record], mode: Mode) -> Result<SleepMap, LogError> {
+    let (sleep_times, problems) = replay(input);
+    match mode {
+        Mode::Reject if !problems.is_empty() => Err(LogError(problems)),
+        _ => Ok(sleep_times),
+    }
+}
+
+// Everything wrong with the log, without working anything out from it
+pub fn check(input: &[Record]) -> Vec<Inconsistency> {
+    replay(input).1
+}
+
+// The naps in the log, leaving out the records that don't add up
+fn replay(input: &[Record]) -> (SleepMap, Vec<Inconsistency>) {
+    let mut guard_on_duty = None;
+    let mut asleep: Option<&Record> = None;
     let mut sleep_times: SleepMap = HashMap::new();
+    let mut problems = Vec::new();
+    let mut report = |rec: &Record, problem| {
+        problems.push(Inconsistency {
+            line: rec.line,
+            time: rec.time,
+            problem,
+        })
+    };
+
     for rec in input {
-        match rec.action {
-            Action::ShiftStart(id) => guard_on_duty = id,
-            Action::Sleep => sleep_start = rec.minute,
-            Action::Wake => {
+        match (&rec.action, guard_on_duty, asleep) {
+            (Action::ShiftStart(id), _, _) => {
+                if let Some(nap) = asleep.take() {
+                    report(nap, Problem::NeverWakes);
+                }
+                guard_on_duty = Some(*id);
+            }
+            (_, None, _) => report(rec, Problem::NoGuard),
+            (Action::Sleep, _, None) => asleep = Some(rec),
+            (Action::Sleep, _, Some(_)) => report(rec, Problem::AlreadyAsleep),
+            (Action::Wake, _, None) => report(rec, Problem::AlreadyAwake),
+            (Action::Wake, Some(id), Some(nap)) => {
                 sleep_times
-                    .entry(guard_on_duty)
-                    .or_insert(Vec::new())
-                    .push(sleep_start as u32..rec.minute as u32);
+                    .entry(id)
+                    .or_default()
+                    .push(nap.time.minutes()..rec.time.minutes());
+                asleep = None;
             }
         }
     }
-    sleep_times
+    if let Some(nap) = asleep {
+        report(nap, Problem::NeverWakes);
+    }
+
+    (sleep_times, problems)
 }
 
+// Both parts reject logs that don't add up. Ties go to the lowest guard
+// ID, and then to the earliest minute, see `part1_candidates` and
+// `part2_candidates` for everything that tied. 0 if nobody ever slept
+// during the midnight hour
 #[aoc(day4, part1)]
-pub fn solve_part1(input: &[Record]) -> u32 {
-    let sleep_times = create_sleep_freq_map(input);
-    let id = find_most_sleep(&sleep_times);
-    let (min, _) = most_minute_slept(id, &sleep_times);
-    id * min
+pub fn solve_part1(input: &[Record]) -> Result<u32, LogError> {
+    Ok(part1_candidates(input)?
+        .first()
+        .map_or(0, Candidate::answer))
 }
 
 #[aoc(day4, part2)]
-pub fn solve_part2(input: &[Record]) -> u32 {
-    let sleep_times = create_sleep_freq_map(input);
+pub fn solve_part2(input: &[Record]) -> Result<u32, LogError> {
+    Ok(part2_candidates(input)?
+        .first()
+        .map_or(0, Candidate::answer))
+}
 
-    let (mut max_min, mut max_freq, mut id) = (0, 0, 0);
+// A guard and minute a strategy could have picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Candidate {
+    pub guard: u32,
+    pub minute: u32,
+}
 
-    for guard_id in sleep_times.keys() {
-        let (min, freq) = most_minute_slept(*guard_id, &sleep_times);
-        if freq > max_freq {
-            max_freq = freq;
-            max_min = min;
-            id = *guard_id;
-        }
+impl Candidate {
+    pub fn answer(&self) -> u32 {
+        self.guard * self.minute
     }
+}
+
+// Strategy 1: every guard tied on the most time asleep, with every minute
+// tied on how often they slept through it. Sorted, so the answer is first
+pub fn part1_candidates(input: &[Record]) -> Result<Vec<Candidate>, LogError> {
+    let sleep_times = create_sleep_freq_map(input, Mode::Reject)?;
+    let total = |naps: &[Range<i64>]| naps.iter().map(|r| r.end - r.start).sum::<i64>();
+    let longest = sleep_times.values().map(|naps| total(naps)).max();
 
-    id * max_min
+    let mut candidates = Vec::new();
+    for (&guard, naps) in &sleep_times {
+        if Some(total(naps)) == longest {
+            let counts = minute_counts(naps);
+            candidates.extend(sleepiest_minutes(&counts).map(|minute| Candidate { guard, minute }));
+        }
+    }
+    candidates.sort_unstable();
+    Ok(candidates)
 }
 
-fn find_most_sleep(sleep_times: &SleepMap) -> u32 {
-    let (mut max_sleep, mut id) = (0, 0);
+// Strategy 2: every guard and minute tied on how often that guard slept
+// through that minute. Sorted, so the answer is first
+pub fn part2_candidates(input: &[Record]) -> Result<Vec<Candidate>, LogError> {
+    let sleep_times = create_sleep_freq_map(input, Mode::Reject)?;
+    let counts: Vec<_> = sleep_times
+        .iter()
+        .map(|(&guard, naps)| (guard, minute_counts(naps)))
+        .collect();
+    let most = counts.iter().flat_map(|(_, c)| c.iter()).max().cloned();
 
-    for (guard_id, ranges) in sleep_times {
-        let time = ranges
-            .iter()
-            .cloned()
-            .fold(0, |acc, r| acc + r.end - r.start);
+    let mut candidates = Vec::new();
+    for (guard, counts) in &counts {
+        if counts.iter().max().cloned() == most {
+            candidates.extend(sleepiest_minutes(counts).map(|minute| Candidate {
+                guard: *guard,
+                minute,
+            }));
+        }
+    }
+    candidates.sort_unstable();
+    Ok(candidates)
+}
 
-        if time > max_sleep {
-            max_sleep = time;
-            id = *guard_id;
+// Only minutes of the midnight hour count, as `00:MM`. A nap that
+// started before midnight still adds to the guard's total sleep
+fn minute_counts(naps: &[Range<i64>]) -> [u32; 60] {
+    let mut counts = [0; 60];
+    for m in naps.iter().cloned().flatten() {
+        let minute = m.rem_euclid(MINUTES_IN_DAY) as usize;
+        if minute < 60 {
+            counts[minute] += 1;
         }
     }
+    counts
+}
 
-    id
+// The minutes slept through most often, unless none were slept through
+fn sleepiest_minutes(counts: &[u32; 60]) -> impl Iterator<Item = u32> + '_ {
+    let most = counts.iter().max().cloned().unwrap_or(0);
+    (0..60).filter(move |&m| most > 0 && counts[m as usize] == most)
 }
 
+// The earliest of the guard's sleepiest minutes and how often they slept
+// through it
 fn most_minute_slept(guard_id: u32, sleep_times: &SleepMap) -> (u32, u32) {
-    let (mut most_frequent_minute, mut max_minute) = (0, 0);
-    let mut freq_map = HashMap::new();
-    let ranges = sleep_times.get(&guard_id).unwrap();
-
-    for r in ranges.clone() {
-        for m in r {
-            let mut e = freq_map.entry(m).or_insert(0);
-            *e += 1;
-            if *e > max_minute {
-                max_minute = *e;
-                most_frequent_minute = m;
+    let counts = minute_counts(&sleep_times[&guard_id]);
+    let minute = sleepiest_minutes(&counts).next().unwrap_or(0);
+    (minute, counts[minute as usize])
+}
+
+// Everything the log says about the guards: when each shift was asleep
+// during the midnight hour, and how each guard does under both strategies
+pub struct Report {
+    // In time order
+    pub shifts: Vec<Shift>,
+    // By ID
+    pub guards: Vec<Guard>,
+}
+
+pub struct Shift {
+    // The midnight the shift covers, which for shifts starting in the
+    // evening is the next day's
+    pub date: Timestamp,
+    pub guard: u32,
+    pub asleep: [bool; 60],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Guard {
+    pub id: u32,
+    // Every minute asleep, in the midnight hour or not
+    pub total: i64,
+    // The minute of the midnight hour most often slept through and how
+    // many times, `None` if the guard never slept in it
+    pub sleepiest: Option<(u32, u32)>,
+}
+
+impl Guard {
+    // What this guard would make the answer, under either strategy
+    pub fn answer(&self) -> Option<u32> {
+        self.sleepiest.map(|(minute, _)| self.id * minute)
+    }
+}
+
+impl Report {
+    pub fn new(input: &[Record], mode: Mode) -> Result<Self, LogError> {
+        let sleep_times = create_sleep_freq_map(input, mode)?;
+
+        // Every shift gets a row, even if the guard stayed awake
+        let mut rows = BTreeMap::new();
+        for rec in input {
+            if let Action::ShiftStart(id) = rec.action {
+                let day = rec.time.minutes().div_euclid(MINUTES_IN_DAY);
+                let day = if rec.time.hour >= 12 { day + 1 } else { day };
+                rows.insert((day, id), [false; 60]);
             }
         }
+        for (&id, naps) in &sleep_times {
+            for m in naps.iter().cloned().flatten() {
+                let minute = m.rem_euclid(MINUTES_IN_DAY) as usize;
+                if minute < 60 {
+                    let day = m.div_euclid(MINUTES_IN_DAY);
+                    rows.entry((day, id)).or_insert([false; 60])[minute] = true;
+                }
+            }
+        }
+        let shifts = rows
+            .into_iter()
+            .map(|((day, guard), asleep)| Shift {
+                date: Timestamp::from_minutes(day * MINUTES_IN_DAY),
+                guard,
+                asleep,
+            })
+            .collect::<Vec<_>>();
+
+        let ids: BTreeSet<u32> = shifts.iter().map(|s| s.guard).collect();
+        let guards = ids
+            .into_iter()
+            .map(|id| match sleep_times.get(&id) {
+                Some(naps) => Guard {
+                    id,
+                    total: naps.iter().map(|r| r.end - r.start).sum(),
+                    sleepiest: Some(most_minute_slept(id, &sleep_times))
+                        .filter(|&(_, freq)| freq > 0),
+                },
+                None => Guard {
+                    id,
+                    total: 0,
+                    sleepiest: None,
+                },
+            })
+            .collect();
+
+        Ok(Report { shifts, guards })
+    }
+
+    // Strategy 1, the guards by how long they slept, longest first and
+    // then by ID like `solve_part1`
+    pub fn by_total(&self) -> Vec<Guard> {
+        let mut ranked = self.guards.clone();
+        ranked.sort_by_key(|g| (Reverse(g.total), g.id));
+        ranked
+    }
+
+    // Strategy 2, the guards by how often they slept through their
+    // sleepiest minute, most often first and then by ID
+    pub fn by_minute(&self) -> Vec<Guard> {
+        let mut ranked = self.guards.clone();
+        ranked.sort_by_key(|g| (Reverse(g.sleepiest.map(|(_, freq)| freq)), g.id));
+        ranked
     }
 
-    (most_frequent_minute, max_minute)
+    // The shifts drawn like the puzzle does, `#` for asleep and `.` for
+    // awake, under a header giving the minutes
+    pub fn chart(&self) -> Vec<String> {
+        let width = self
+            .guards
+            .iter()
+            .map(|g| g.id.to_string().len() + 1)
+            .max()
+            .unwrap_or(0)
+            .max(2);
+        let digits = |place: usize| {
+            (0..60)
+                .map(|m| (m / place % 10).to_string())
+                .collect::<String>()
+        };
+        let mut lines = vec![
+            format!("Date   {:w$}  Minute", "ID", w = width),
+            format!("       {:w$}  {}", "", digits(10), w = width),
+            format!("       {:w$}  {}", "", digits(1), w = width),
+        ];
+        for shift in &self.shifts {
+            let minutes: String = shift
+                .asleep
+                .iter()
+                .map(|&a| if a { '#' } else { '.' })
+                .collect();
+            lines.push(format!(
+                "{:02}-{:02}  {:w$}  {}",
+                shift.date.month,
+                shift.date.day,
+                format!("#{}", shift.guard),
+                minutes,
+                w = width
+            ));
+        }
+        lines
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in self.chart() {
+            writeln!(f, "{}", line)?;
+        }
+
+        let sleepiest = |g: &Guard| match g.sleepiest {
+            Some((minute, freq)) => format!("00:{:02} ({} times)", minute, freq),
+            None => "-".to_owned(),
+        };
+        let answer = |g: &Guard| g.answer().map_or("-".to_owned(), |a| a.to_string());
+        writeln!(f)?;
+        writeln!(f, "Guard      Asleep  Sleepiest minute")?;
+        for g in &self.guards {
+            let id = format!("#{}", g.id);
+            writeln!(f, "{:9}  {:6}  {}", id, g.total, sleepiest(g))?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Strategy 1, most minutes asleep")?;
+        for (rank, g) in self.by_total().iter().enumerate() {
+            let id = format!("#{}", g.id);
+            writeln!(f, "{:3}. {:9}  {:6}  {}", rank + 1, id, g.total, answer(g))?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Strategy 2, most often asleep on the same minute")?;
+        for (rank, g) in self.by_minute().iter().enumerate() {
+            let id = format!("#{}", g.id);
+            let freq = g.sleepiest.map_or(0, |(_, freq)| freq);
+            writeln!(f, "{:3}. {:9}  {:6}  {}", rank + 1, id, freq, answer(g))?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::generators;
 
     #[test]
     fn test_input() {
-        let input = input_gen(include_str!("../input/tests/d4.txt"));
-        assert_eq!(solve_part1(&input), 240);
+        let input = input_gen(include_str!("../input/tests/d4.txt")).unwrap();
+        assert_eq!(solve_part1(&input).unwrap(), 240);
+    }
+
+    #[test]
+    fn test_bad_record() {
+        let input = "[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:05] dozes off";
+        let err = input_gen(input).unwrap_err();
+        assert_eq!((err.day, err.line), (4, 2));
+
+        let err = input_gen("[1518-11-01 00:00] Guard #ten begins shift").unwrap_err();
+        assert_eq!(err.line, 1);
+
+        let err = input_gen("[1518-02-29 00:00] Guard #10 begins shift").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(input_gen("[1600-02-29 00:00] Guard #10 begins shift").is_ok());
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let time = |text: &str| input_gen(&format!("[{}] wakes up", text)).unwrap()[0].time;
+        let minutes = |a, b| time(b).minutes() - time(a).minutes();
+        assert_eq!(minutes("1518-12-31 23:58", "1519-01-01 00:05"), 7);
+        assert_eq!(minutes("1518-02-28 23:00", "1518-03-01 00:00"), 60);
+        assert_eq!(
+            minutes("1600-02-28 23:00", "1600-03-01 00:00"),
+            24 * 60 + 60
+        );
+        assert_eq!(
+            minutes("1518-01-01 00:00", "1519-01-01 00:00"),
+            365 * 24 * 60
+        );
+        assert!(time("1518-12-31 23:58") < time("1519-01-01 00:00"));
+
+        for text in &["1518-12-31 23:58", "1600-02-29 00:00", "1900-03-01 12:34"] {
+            assert_eq!(Timestamp::from_minutes(time(text).minutes()), time(text));
+            assert_eq!(time(text).to_string(), *text);
+        }
+    }
+
+    // Shifts starting before midnight, across the end of a month and a year
+    #[test]
+    fn test_sleep_over_midnight() {
+        let input = "[1519-01-01 00:10] wakes up
+[1518-12-31 23:50] Guard #7 begins shift
+[1518-12-31 23:58] falls asleep
+[1519-01-01 00:20] falls asleep
+[1519-01-01 00:25] wakes up
+[1519-01-31 23:59] Guard #3 begins shift
+[1519-02-01 00:04] falls asleep
+[1519-02-01 00:26] wakes up
+[1519-02-01 23:57] Guard #7 begins shift
+[1519-02-02 00:03] falls asleep
+[1519-02-02 00:09] wakes up
+";
+        let records = input_gen(input).unwrap();
+        assert_eq!(records[0].action, Action::ShiftStart(7));
+        let sleep = create_sleep_freq_map(&records, Mode::Reject).unwrap();
+        assert_eq!(sleep[&7].iter().map(|r| r.end - r.start).sum::<i64>(), 23);
+
+        // Guard 7 only slept longer than guard 3 counting 23:58 and 23:59,
+        // and was asleep most often on 00:03 to 00:08
+        assert_eq!(most_minute_slept(7, &sleep), (3, 2));
+        assert_eq!(most_minute_slept(3, &sleep), (4, 1));
+        assert_eq!(solve_part1(&records).unwrap(), 7 * 3);
+        assert_eq!(solve_part2(&records).unwrap(), 7 * 3);
+    }
+
+    #[test]
+    fn test_inconsistent_log() {
+        let input = "[1518-10-31 23:30] falls asleep
+[1518-11-01 00:00] Guard #10 begins shift
+[1518-11-01 00:05] falls asleep
+[1518-11-01 00:07] falls asleep
+[1518-11-01 00:25] wakes up
+[1518-11-01 00:30] wakes up
+[1518-11-01 00:40] falls asleep
+[1518-11-01 23:58] Guard #99 begins shift
+[1518-11-02 00:10] falls asleep
+[1518-11-02 00:20] wakes up
+[1518-11-02 00:50] falls asleep
+";
+        let records = input_gen(input).unwrap();
+        let problems = check(&records);
+        let found: Vec<_> = problems.iter().map(|i| (i.line, i.problem)).collect();
+        assert_eq!(
+            found,
+            vec![
+                (1, Problem::NoGuard),
+                (4, Problem::AlreadyAsleep),
+                (6, Problem::AlreadyAwake),
+                (7, Problem::NeverWakes),
+                (11, Problem::NeverWakes),
+            ]
+        );
+        assert_eq!(
+            problems[1].to_string(),
+            "line 4 [1518-11-01 00:07]: falls asleep while already asleep"
+        );
+
+        let err = create_sleep_freq_map(&records, Mode::Reject).unwrap_err();
+        assert_eq!(err.0, problems);
+        assert_eq!(solve_part1(&records), Err(err));
+
+        // Guard 10 slept from the first time they fell asleep, and the
+        // naps nobody woke up from are gone
+        let sleep = create_sleep_freq_map(&records, Mode::Repair).unwrap();
+        let start = |id: u32| sleep[&id].iter().map(|r| r.start % 60).collect::<Vec<_>>();
+        assert_eq!((start(10), start(99)), (vec![5], vec![10]));
+        assert_eq!(sleep[&10][0].end - sleep[&10][0].start, 20);
+        assert!(check(&input_gen(include_str!("../input/tests/d4.txt")).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_report() {
+        let input = input_gen(include_str!("../input/tests/d4.txt")).unwrap();
+        let report = Report::new(&input, Mode::Reject).unwrap();
+        let chart = "\
+Date   ID   Minute
+            000000000011111111112222222222333333333344444444445555555555
+            012345678901234567890123456789012345678901234567890123456789
+11-01  #10  .....####################.....#########################.....
+11-02  #99  ........................................##########..........
+11-03  #10  ........................#####...............................
+11-04  #99  ....................................##########..............
+11-05  #99  .............................................##########.....";
+        assert_eq!(report.chart().join("\n"), chart);
+
+        let ten = Guard {
+            id: 10,
+            total: 50,
+            sleepiest: Some((24, 2)),
+        };
+        let ninety_nine = Guard {
+            id: 99,
+            total: 30,
+            sleepiest: Some((45, 3)),
+        };
+        assert_eq!(report.guards, vec![ten, ninety_nine]);
+        assert_eq!(report.by_total(), vec![ten, ninety_nine]);
+        assert_eq!(report.by_minute(), vec![ninety_nine, ten]);
+        assert_eq!(report.by_total()[0].answer(), Some(240));
+        assert_eq!(report.by_minute()[0].answer(), Some(4455));
+
+        let text = report.to_string();
+        assert!(text.starts_with(chart));
+        assert!(text.contains("#10            50  00:24 (2 times)\n"));
+        assert!(text.contains("  1. #99             3  4455\n"));
+
+        // A shift that starts the evening before, and one spent awake
+        let input = input_gen(
+            "[1518-12-31 23:50] Guard #7 begins shift
+[1518-12-31 23:58] falls asleep
+[1519-01-01 00:02] wakes up
+[1519-01-01 23:59] Guard #1234 begins shift",
+        )
+        .unwrap();
+        let report = Report::new(&input, Mode::Reject).unwrap();
+        let chart = report.chart();
+        assert_eq!(chart[0], "Date   ID     Minute");
+        assert!(chart[3].starts_with("01-01  #7     ##..."));
+        assert!(chart[4].starts_with("01-02  #1234  ....."));
+        assert_eq!(report.guards[1].sleepiest, None);
+        assert_eq!(report.by_total()[1].total, 0);
+    }
+
+    // How many times each guard was asleep on each minute
+    fn minutes_asleep(records: &[Record]) -> HashMap<u32, [u32; 60]> {
+        let (mut guard, mut asleep) = (0, 0);
+        let mut minutes = HashMap::new();
+        for r in records {
+            match r.action {
+                Action::ShiftStart(id) => guard = id,
+                Action::Sleep => asleep = r.time.minute as usize,
+                Action::Wake => {
+                    let counts = minutes.entry(guard).or_insert([0; 60]);
+                    for count in &mut counts[asleep..r.time.minute as usize] {
+                        *count += 1;
+                    }
+                }
+            }
+        }
+        minutes
+    }
+
+    // Every item with the largest key
+    fn all_max<T, I: Iterator<Item = (u32, T)>>(items: I) -> Vec<T> {
+        let items: Vec<_> = items.collect();
+        let most = items.iter().map(|&(key, _)| key).max();
+        items
+            .into_iter()
+            .filter(|&(key, _)| Some(key) == most)
+            .map(|(_, item)| item)
+            .collect()
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        generators::check(200, |rng| {
+            let records = input_gen(&generators::day4(rng)).unwrap();
+            let minutes = minutes_asleep(&records);
+            let sleepiest = |id: u32| all_max((0..60).map(|m| (minutes[&id][m], m as u32)));
+            let candidate = |(guard, minute)| Candidate { guard, minute };
+
+            let mut longest = all_max(minutes.iter().map(|(&id, c)| (c.iter().sum(), id)));
+            longest.sort_unstable();
+            let mut tied: Vec<_> = longest
+                .iter()
+                .flat_map(|&id| sleepiest(id).into_iter().map(move |m| (id, m)))
+                .map(candidate)
+                .collect();
+            tied.sort_unstable();
+            assert_eq!(part1_candidates(&records).unwrap(), tied);
+            let (id, minute) = (longest[0], sleepiest(longest[0])[0]);
+            assert_eq!(solve_part1(&records).unwrap(), id * minute);
+
+            let guard_minutes = minutes
+                .iter()
+                .flat_map(|(&id, c)| (0..60).map(move |m| (c[m], (id, m as u32))));
+            let mut tied: Vec<_> = all_max(guard_minutes).into_iter().map(candidate).collect();
+            tied.sort_unstable();
+            assert_eq!(part2_candidates(&records).unwrap(), tied);
+            assert_eq!(
+                solve_part2(&records).unwrap(),
+                tied[0].guard * tied[0].minute
+            );
+        });
+    }
+
+    #[test]
+    fn test_ties() {
+        let input = input_gen(
+            "[1518-11-01 00:00] Guard #5 begins shift
+[1518-11-01 00:05] falls asleep
+[1518-11-01 00:15] wakes up
+[1518-11-02 00:00] Guard #3 begins shift
+[1518-11-02 00:10] falls asleep
+[1518-11-02 00:20] wakes up
+[1518-11-03 00:00] Guard #3 begins shift
+[1518-11-03 00:30] falls asleep
+[1518-11-03 00:31] wakes up
+[1518-11-04 00:00] Guard #5 begins shift
+[1518-11-04 00:40] falls asleep
+[1518-11-04 00:41] wakes up",
+        )
+        .unwrap();
+
+        // Both slept 11 minutes, each minute once
+        let part1 = part1_candidates(&input).unwrap();
+        assert_eq!(part1.len(), 22);
+        assert_eq!(
+            part1[0],
+            Candidate {
+                guard: 3,
+                minute: 10
+            }
+        );
+        assert_eq!(
+            part1[21],
+            Candidate {
+                guard: 5,
+                minute: 40
+            }
+        );
+        assert_eq!(solve_part1(&input).unwrap(), 3 * 10);
+        assert_eq!(part2_candidates(&input).unwrap(), part1);
+        assert_eq!(solve_part2(&input).unwrap(), 3 * 10);
+
+        // Every run hashes the guards differently
+        for _ in 0..10 {
+            assert_eq!(solve_part1(&input).unwrap(), 30);
+        }
+        assert_eq!(solve_part1(&[]).unwrap(), 0);
+        assert!(part1_candidates(&[]).unwrap().is_empty());
     }
 }
@@ -1,57 +1,339 @@
-use regex::Regex;
-use std::borrow::Cow;
-
-lazy_static! {
-    static ref regexp: Regex = Regex::new(
-        r"(?x)
-        (aA|Aa|bB|Bb|cC|Cc|dD|Dd|eE|Ee|fF|Ff|gG|Gg
-        |hH|Hh|iI|Ii|jJ|Jj|kK|Kk|lL|Ll|mM|Mm|nN|Nn
-        |oO|Oo|pP|Pp|qQ|Qq|rR|Rr|sS|Ss|tT|Tt|uU|Uu
-        |vV|Vv|wW|Ww|xX|Xx|yY|Yy|zZ|Zz)
-    "
-    )
-    .unwrap();
-}
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, BufRead};
+use std::{str, thread};
 
 #[aoc(day5, part1)]
 pub fn solve_part1(input: &str) -> usize {
-    react_polymer(input.to_owned())
+    react(input, &Rules::ascii_case()).chars().count()
 }
 
 #[aoc(day5, part2)]
 pub fn solve_part2(input: &str) -> usize {
-    let lowercase_alpha = (b'a'..=b'z')
-        .map(|c| c as char)
-        .filter(|c| c.is_ascii_lowercase())
-        .collect::<Vec<char>>();
-    let mut min_length = input.len();
-    for c in lowercase_alpha {
-        let resulting_polymer = remove_one_type(input, c);
-        let curr_length = react_polymer(resulting_polymer);
-        if min_length > curr_length {
-            min_length = curr_length;
+    shortest_removal(&react(input, &Rules::ascii_case()))
+}
+
+// Only the units that haven't reacted yet are kept, however long the polymer
+pub fn solve_part1_from<R: BufRead>(reader: R) -> io::Result<usize> {
+    Ok(react_from(reader, &Rules::ascii_case())?.chars().count())
+}
+
+pub fn solve_part2_from<R: BufRead>(reader: R) -> io::Result<usize> {
+    Ok(shortest_removal(&react_from(reader, &Rules::ascii_case())?))
+}
+
+// Units that react with each other would react just the same with some
+// other type taken out, so the removals start from the reacted polymer
+fn shortest_removal(reacted: &str) -> usize {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    Removals::new(reacted, &Rules::ascii_case(), threads)
+        .best()
+        .map_or(reacted.chars().count(), |(_, len)| len)
+}
+
+// The units left once the polymer is fully reacted, in order. Whitespace
+// and a leading byte order mark aren't part of the polymer
+pub fn react(polymer: &str, rules: &Rules) -> String {
+    let polymer = polymer.strip_prefix('\u{feff}').unwrap_or(polymer);
+    let mut reaction = Reaction::new(rules, None);
+    reaction.push_str(polymer);
+    reaction.into_units()
+}
+
+// `react` for a polymer being read
+pub fn react_from<R: BufRead>(reader: R, rules: &Rules) -> io::Result<String> {
+    let mut reaction = Reaction::new(rules, None);
+    feed(reader, |unit| reaction.push(unit))?;
+    Ok(reaction.into_units())
+}
+
+// How long a reacted polymer ends up with each type of unit taken out
+pub struct Removals {
+    // By the type's unit, see `Rules::unit_type`. Only types left in the
+    // polymer are here, taking out any other leaves it as it is
+    pub lengths: BTreeMap<char, usize>,
+}
+
+impl Removals {
+    // The types are split between `threads` threads, 1 doesn't start any
+    pub fn new(reacted: &str, rules: &Rules, threads: usize) -> Self {
+        let mut types: Vec<char> = reacted
+            .chars()
+            .filter(|u| !u.is_ascii_whitespace())
+            .map(|u| rules.unit_type(u))
+            .collect();
+        types.sort_unstable();
+        types.dedup();
+
+        let react_without = |removed: &[char]| -> Vec<(char, usize)> {
+            removed
+                .iter()
+                .map(|&t| {
+                    let mut reaction = Reaction::new(rules, Some(t));
+                    reaction.push_str(reacted);
+                    (t, reaction.len())
+                })
+                .collect()
+        };
+        let chunk = types.len().div_ceil(threads.max(1)).max(1);
+        let lengths = if threads <= 1 {
+            react_without(&types)
+        } else {
+            thread::scope(|s| {
+                let handles: Vec<_> = types
+                    .chunks(chunk)
+                    .map(|removed| s.spawn(move || react_without(removed)))
+                    .collect();
+                handles
+                    .into_iter()
+                    .flat_map(|h| h.join().expect("reacting a polymer"))
+                    .collect()
+            })
+        };
+
+        Removals {
+            lengths: lengths.into_iter().collect(),
+        }
+    }
+
+    // The type to take out for the shortest polymer and how long it is,
+    // the first in the alphabet if several are as short
+    pub fn best(&self) -> Option<(char, usize)> {
+        self.lengths
+            .iter()
+            .min_by_key(|&(&t, &len)| (len, t))
+            .map(|(&t, &len)| (t, len))
+    }
+}
+
+// Which pairs of units react. Built from one of the kinds of pair, and
+// then made one-directional if only some orders should react
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+    pairs: Pairs,
+    one_way: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Pairs {
+    AsciiCase,
+    UnicodeCase,
+    Listed(HashSet<(char, char)>),
+}
+
+impl Rules {
+    // The puzzle's, `aA` and `Aa` for every ASCII letter
+    pub fn ascii_case() -> Self {
+        Rules {
+            pairs: Pairs::AsciiCase,
+            one_way: false,
+        }
+    }
+
+    // Any lowercase letter with its uppercase, like `σΣ` or `éÉ`
+    pub fn unicode_case() -> Self {
+        Rules {
+            pairs: Pairs::UnicodeCase,
+            one_way: false,
+        }
+    }
+
+    // Just these pairs, given as (left, right)
+    pub fn pairs<I: IntoIterator<Item = (char, char)>>(pairs: I) -> Self {
+        Rules {
+            pairs: Pairs::Listed(pairs.into_iter().collect()),
+            one_way: false,
+        }
+    }
+
+    // Only react when the pair comes the way round it was given, and for
+    // case pairs when the lowercase unit comes first
+    pub fn one_way(mut self) -> Self {
+        self.one_way = true;
+        self
+    }
+
+    // Whether `right` reacts with `left` when it lands next to it
+    pub fn reacts(&self, left: char, right: char) -> bool {
+        let forward = |left: char, right: char| match &self.pairs {
+            Pairs::AsciiCase => left.is_ascii_lowercase() && left.to_ascii_uppercase() == right,
+            Pairs::UnicodeCase => {
+                left.is_lowercase() && right.is_uppercase() && right.to_lowercase().eq(Some(left))
+            }
+            Pairs::Listed(pairs) => pairs.contains(&(left, right)),
+        };
+        forward(left, right) || !self.one_way && forward(right, left)
+    }
+
+    // The unit that stands for all of `unit`'s type when taking a type
+    // out: its lowercase for case pairs, and just itself for listed ones
+    pub fn unit_type(&self, unit: char) -> char {
+        match self.pairs {
+            Pairs::AsciiCase => unit.to_ascii_lowercase(),
+            Pairs::UnicodeCase => {
+                let mut lower = unit.to_lowercase();
+                match (lower.next(), lower.next()) {
+                    (Some(l), None) => l,
+                    _ => unit,
+                }
+            }
+            Pairs::Listed(_) => unit,
+        }
+    }
+}
+
+// Hands every unit to `react` as it's read, whitespace and a byte order
+// mark aren't part of the polymer. Units are decoded as UTF-8, one may be
+// split between reads
+fn feed<R: BufRead, F: FnMut(char)>(mut reader: R, mut react: F) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(4);
+    let mut at_start = true;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        for &byte in buf {
+            bytes.push(byte);
+            let unit = match str::from_utf8(&bytes) {
+                Ok(unit) => unit.chars().next().unwrap(),
+                // The rest of it is still to come
+                Err(e) if e.error_len().is_none() => continue,
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            };
+            bytes.clear();
+            if !(at_start && unit == '\u{feff}' || unit.is_ascii_whitespace()) {
+                react(unit);
+            }
+            at_start = false;
+        }
+        let read = buf.len();
+        reader.consume(read);
+    }
+
+    match str::from_utf8(&bytes) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+    }
+}
+
+// A polymer built up a unit at a time. A new unit can only react with the
+// last one left standing, so they're kept on a stack. The puzzle's rules
+// only ever react two ASCII letters, so those polymers are kept as bytes
+enum Reaction<'a> {
+    Ascii {
+        // Units of this type are dropped before they can react
+        removed: Option<char>,
+        units: Vec<u8>,
+    },
+    Chars {
+        rules: &'a Rules,
+        removed: Option<char>,
+        units: Vec<char>,
+    },
+}
+
+impl<'a> Reaction<'a> {
+    fn new(rules: &'a Rules, removed: Option<char>) -> Self {
+        if rules.pairs == Pairs::AsciiCase && !rules.one_way {
+            Reaction::Ascii {
+                removed,
+                units: Vec::new(),
+            }
+        } else {
+            Reaction::Chars {
+                rules,
+                removed,
+                units: Vec::new(),
+            }
+        }
+    }
+
+    // Every unit in `polymer`, leaving out whitespace
+    fn push_str(&mut self, polymer: &str) {
+        match self {
+            Reaction::Ascii { removed, units } if removed.is_none_or(|r| r.is_ascii()) => {
+                let removed = removed.map(|r| r as u8);
+                for &unit in polymer.as_bytes() {
+                    if !unit.is_ascii_whitespace() && Some(unit.to_ascii_lowercase()) != removed {
+                        push_byte(units, unit);
+                    }
+                }
+            }
+            _ => {
+                for unit in polymer.chars().filter(|u| !u.is_ascii_whitespace()) {
+                    self.push(unit);
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, unit: char) {
+        match self {
+            Reaction::Ascii { removed, units } => {
+                if Some(unit.to_ascii_lowercase()) != *removed {
+                    let mut bytes = [0; 4];
+                    for &byte in unit.encode_utf8(&mut bytes).as_bytes() {
+                        push_byte(units, byte);
+                    }
+                }
+            }
+            Reaction::Chars {
+                rules,
+                removed,
+                units,
+            } => {
+                if removed.is_some() && Some(rules.unit_type(unit)) == *removed {
+                    return;
+                }
+                match units.last() {
+                    Some(&last) if rules.reacts(last, unit) => {
+                        units.pop();
+                    }
+                    _ => units.push(unit),
+                }
+            }
         }
     }
 
-    min_length
+    // In units, not bytes
+    fn len(&self) -> usize {
+        match self {
+            Reaction::Ascii { units, .. } => units.iter().filter(|&&b| !is_continuation(b)).count(),
+            Reaction::Chars { units, .. } => units.len(),
+        }
+    }
+
+    fn into_units(self) -> String {
+        match self {
+            // Only whole ASCII letters are taken out, so what's left is
+            // still valid UTF-8
+            Reaction::Ascii { units, .. } => String::from_utf8_lossy(&units).into_owned(),
+            Reaction::Chars { units, .. } => units.into_iter().collect(),
+        }
+    }
 }
 
-pub fn react_polymer(mut polymer: String) -> usize {
-    while regexp.is_match(&polymer) {
-        polymer = regexp.replace_all(&polymer, "").into_owned();
+// `aA` and `Aa` react. No byte of a char that isn't ASCII is a letter, so
+// those never do
+fn push_byte(units: &mut Vec<u8>, unit: u8) {
+    match units.last() {
+        Some(&last) if last ^ unit == 0x20 && last.is_ascii_alphabetic() => {
+            units.pop();
+        }
+        _ => units.push(unit),
     }
-    polymer.trim().len()
 }
 
-pub fn remove_one_type(polymer: &str, polymer_type: char) -> String {
-    let uppercase = polymer_type.to_uppercase().to_string();
-    let type_regexp = Regex::new(&format!("({}|{})", polymer_type, uppercase,)).unwrap();
-    type_regexp.replace_all(polymer, "").into_owned()
+// Every byte of a multi-byte UTF-8 char after the first
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xc0 == 0x80
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::generators;
+    use std::io::BufReader;
 
     #[test]
     fn test_part1() {
@@ -61,11 +343,137 @@ mod test {
         assert_eq!(solve_part1(input), 10);
         assert_eq!(solve_part1(input2), input2.len());
         assert_eq!(solve_part1(input3), 0);
+
+        let ascii = Rules::ascii_case();
+        assert_eq!(react("dabAcCaCBAcCcaDA", &ascii), "dabCBAcaDA");
+        assert_eq!(react_from(input.as_bytes(), &ascii).unwrap(), "dabCBAcaDA");
+        assert_eq!(react("", &ascii), "");
+
+        // Whitespace and a leading BOM are left out however the polymer
+        // comes in
+        let spaced = "\u{feff} d a\tA\n b\r\n";
+        assert_eq!(react(spaced, &ascii), "db");
+        assert_eq!(react_from(spaced.as_bytes(), &ascii).unwrap(), "db");
+        assert_eq!(react(spaced, &Rules::unicode_case()), "db");
+        assert_eq!(solve_part1(spaced), 2);
+        assert_eq!(solve_part2(spaced), 1);
+        assert_eq!(solve_part1_from(spaced.as_bytes()).unwrap(), 2);
+
+        assert_eq!(solve_part1_from("\u{feff}aA".as_bytes()).unwrap(), 0);
+        let reader = BufReader::with_capacity(1, "\u{feff}abBc\n".as_bytes());
+        assert_eq!(react_from(reader, &ascii).unwrap(), "ac");
+        // A BOM only at the start, and only valid UTF-8
+        assert_eq!(
+            react_from("a\u{feff}".as_bytes(), &ascii).unwrap(),
+            "a\u{feff}"
+        );
+        let err = react_from(&b"\xef\xbbx"[..], &ascii).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(react_from(&b"ab\xef\xbb"[..], &ascii).is_err());
+
+        // Nothing reacts until the middle, then everything does
+        let nested = "x".repeat(500_000) + &"X".repeat(500_000);
+        assert_eq!(react(&nested, &ascii), "");
     }
 
     #[test]
     fn test_part2() {
         let input = include_str!("../input/tests/d5.txt");
         assert_eq!(solve_part2(input), 4);
+
+        let ascii = Rules::ascii_case();
+        let reacted = react(input.trim(), &ascii);
+        for threads in 1..=5 {
+            let removals = Removals::new(&reacted, &ascii, threads);
+            let lengths: Vec<_> = removals.lengths.into_iter().collect();
+            assert_eq!(
+                lengths,
+                vec![('a', 6), ('b', 8), ('c', 4), ('d', 6)],
+                "{} threads",
+                threads
+            );
+        }
+        assert_eq!(Removals::new(&reacted, &ascii, 4).best(), Some(('c', 4)));
+        assert_eq!(Removals::new("cab", &ascii, 2).best(), Some(('a', 2)));
+        assert_eq!(Removals::new("", &ascii, 3).best(), None);
+    }
+
+    #[test]
+    fn test_rules() {
+        let ascii = Rules::ascii_case();
+        assert_eq!(react("éÉσΣ", &ascii), "éÉσΣ");
+        assert_eq!(react("aébBAÉ", &ascii), "aéAÉ");
+        let removals = Removals::new("aéA", &ascii, 1);
+        let lengths: Vec<_> = removals.lengths.into_iter().collect();
+        assert_eq!(lengths, vec![('a', 1), ('é', 0)]);
+        assert_eq!(react("aAAa", &ascii.clone().one_way()), "Aa");
+
+        let unicode = Rules::unicode_case();
+        assert_eq!(react("xéσΣÉ", &unicode), "x");
+        assert_eq!(react("aAÉé", &unicode.clone().one_way()), "Éé");
+        // Two lowercase letters aren't a case pair, whatever they lowercase to
+        assert!(!unicode.reacts('ς', 'σ'));
+        // Units split between reads still react
+        let reader = BufReader::with_capacity(1, "\u{feff}éσ\nΣÉx".as_bytes());
+        assert_eq!(react_from(reader, &unicode).unwrap(), "x");
+        let removals = Removals::new("éσÉxΣ", &unicode, 2);
+        let lengths: Vec<_> = removals.lengths.into_iter().collect();
+        assert_eq!(lengths, vec![('x', 4), ('é', 3), ('σ', 1)]);
+
+        let brackets = Rules::pairs(vec![('(', ')'), ('[', ']')]);
+        assert_eq!(react(")([()])(", &brackets), "");
+        assert_eq!(react(")([()])(", &brackets.clone().one_way()), ")(");
+        assert_eq!(react("aA", &brackets), "aA");
+        assert_eq!(react("", &brackets), "");
+        let removals = Removals::new("([)]", &brackets.one_way(), 1);
+        assert_eq!(removals.best(), Some((')', 1)));
+    }
+
+    // Removes the first reacting pair until there are none
+    fn reduce(polymer: &str) -> String {
+        let mut units: Vec<char> = polymer.trim().chars().collect();
+        while let Some(i) = (1..units.len())
+            .find(|&i| units[i - 1] != units[i] && units[i - 1].eq_ignore_ascii_case(&units[i]))
+        {
+            units.drain(i - 1..=i);
+        }
+        units.into_iter().collect()
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        generators::check(100, |rng| {
+            let polymer = generators::day5(rng);
+            let reduced = reduce(&polymer);
+            assert_eq!(react(polymer.trim(), &Rules::ascii_case()), reduced);
+            assert_eq!(react(polymer.trim(), &Rules::unicode_case()), reduced);
+            assert_eq!(solve_part1(&polymer), reduced.len());
+
+            let shortest = (b'a'..=b'z')
+                .map(|unit| {
+                    let removed =
+                        polymer.replace(|c: char| c.to_ascii_lowercase() == unit as char, "");
+                    reduce(&removed).len()
+                })
+                .min();
+            assert_eq!(Some(solve_part2(&polymer)), shortest);
+
+            let ascii = Rules::ascii_case();
+            let removals = Removals::new(&react(polymer.trim(), &ascii), &ascii, 1 + rng.below(4));
+            for (&t, &len) in &removals.lengths {
+                let removed = polymer.replace(|c: char| c.to_ascii_lowercase() == t, "");
+                assert_eq!(reduce(&removed).len(), len);
+            }
+        });
+    }
+
+    #[test]
+    fn test_read_stream() {
+        generators::check(100, |rng| {
+            let polymer = generators::day5(rng);
+            let mut reader = || BufReader::with_capacity(1 + rng.below(4), polymer.as_bytes());
+            assert_eq!(solve_part1_from(reader()).unwrap(), solve_part1(&polymer));
+            assert_eq!(solve_part2_from(reader()).unwrap(), solve_part2(&polymer));
+        });
     }
 }
@@ -1,13 +1,17 @@
+use crate::error::{self, Line, ParseError};
+use crate::grid::{self, Grid};
+use crate::input;
+use crate::render::{self, Frames, Image, RenderError, Rgb};
 use kdtree::KdTree;
 use std::{
     cmp,
     collections::{HashMap, HashSet},
     f32,
-    num::ParseIntError,
     str::FromStr,
-    u32::MAX,
 };
 
+const DAY: u8 = 6;
+
 #[cfg(not(test))]
 const PART2_CUTOFF: f32 = 10000.0;
 
@@ -17,13 +21,26 @@ fn manhattan_distance(p1: &[f32], p2: &[f32]) -> f32 {
         .fold(0.0, |acc, (a, b)| acc + f32::abs(a - b))
 }
 
+// The solvers compare each location's two nearest coordinates, so there
+// have to be at least two
 #[aoc_generator(day6)]
-fn input_gen(input: &str) -> Vec<Point> {
-    input.lines().map(|l| l.parse::<Point>().unwrap()).collect()
+pub fn input_gen(input: &str) -> Result<Vec<Point>, ParseError> {
+    let input = &input::normalize(input);
+    let points: Vec<Point> = error::lines(DAY, input)
+        .map(|l| l.parse_as())
+        .collect::<Result<_, _>>()?;
+    if points.len() < 2 {
+        return Err(ParseError::missing(
+            DAY,
+            points.len() + 1,
+            "at least two coordinates",
+        ));
+    }
+    Ok(points)
 }
 
 #[aoc(day6, part1)]
-fn solve_part1(input: &[Point]) -> u32 {
+pub fn solve_part1(input: &[Point]) -> u32 {
     let (rect, points_tree) = process_tree_and_rect(input);
     let mut infinite_areas = HashSet::<usize>::new();
 
@@ -57,7 +74,7 @@ fn solve_part1(input: &[Point]) -> u32 {
 }
 
 #[aoc(day6, part2)]
-fn solve_part2(input: &[Point]) -> usize {
+pub fn solve_part2(input: &[Point]) -> usize {
     let (rect, points_tree) = process_tree_and_rect(input);
     let mut points_in_region = HashSet::new();
 
@@ -77,8 +94,38 @@ fn solve_part2(input: &[Point]) -> usize {
     points_in_region.len()
 }
 
+// Every location coloured by the coordinate it's closest to, with a
+// border so the infinite areas can be seen running off the edge. Ties are
+// grey and the coordinates themselves white
+pub fn render(input: &[Point], frames: &mut Frames) -> Result<(), RenderError> {
+    const BORDER: usize = 10;
+    let width = input.iter().map(|p| p.x as usize).max().unwrap_or(0) + 2 * BORDER;
+    let height = input.iter().map(|p| p.y as usize).max().unwrap_or(0) + 2 * BORDER;
+    let closest = Grid::from_fn(width, height, |c: grid::Point| {
+        let (x, y) = (c.x as i64 - BORDER as i64, c.y as i64 - BORDER as i64);
+        let distances: Vec<i64> = input
+            .iter()
+            .map(|p| (i64::from(p.x) - x).abs() + (i64::from(p.y) - y).abs())
+            .collect();
+        let nearest = distances.iter().min().cloned().unwrap_or(0);
+        match distances.iter().filter(|&&d| d == nearest).count() {
+            1 => Some((
+                distances.iter().position(|&d| d == nearest).unwrap(),
+                nearest,
+            )),
+            _ => None,
+        }
+    });
+
+    frames.push(Image::from_grid(&closest, |c| match c {
+        Some((_, 0)) => Rgb::WHITE,
+        Some((i, _)) => render::distinct(*i).fade(180),
+        None => Rgb(90, 90, 90),
+    }))
+}
+
 fn process_tree_and_rect(points: &[Point]) -> (Rectangle, PointsTree) {
-    let (mut min_x, mut max_x, mut min_y, mut max_y) = (MAX, 0, MAX, 0);
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (u32::MAX, 0, u32::MAX, 0);
     let mut tree = KdTree::new_with_capacity(2, points.len());
 
     // The idea here is to create the smallest bounding rectangle that contains all
@@ -88,13 +135,13 @@ fn process_tree_and_rect(points: &[Point]) -> (Rectangle, PointsTree) {
         min_y = cmp::min(min_y, p.y);
         max_x = cmp::max(max_x, p.x);
         max_y = cmp::max(max_y, p.y);
-        tree.add([p.x as f32, p.y as f32], i);
+        tree.add([p.x as f32, p.y as f32], i).unwrap();
     }
 
     (
         Rectangle {
             upper_left: Point::new(min_x, min_y),
-            upper_right: Point::new(max_y, min_y),
+            upper_right: Point::new(max_x, min_y),
             lower_left: Point::new(min_x, max_y),
             lower_right: Point::new(max_x, max_y),
         },
@@ -115,13 +162,17 @@ impl Point {
 }
 
 impl FromStr for Point {
-    type Err = ParseIntError;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let coords: Vec<&str> = s.split(",").map(|s| s.trim()).collect();
+        let line = Line::new(DAY, 1, s);
+        let coords: Vec<&str> = s.split(',').map(|s| s.trim()).collect();
+        if coords.len() != 2 {
+            return Err(line.malformed("a point like `1, 6`"));
+        }
         Ok(Point {
-            x: coords[0].parse::<u32>()?,
-            y: coords[1].parse::<u32>()?,
+            x: line.parse(coords[0])?,
+            y: line.parse(coords[1])?,
         })
     }
 }
@@ -151,7 +202,7 @@ impl Rectangle {
         points
     }
 
-    fn points_iter(&self) -> PointsIter {
+    fn points_iter(&self) -> PointsIter<'_> {
         PointsIter {
             rect: self,
             px: self.upper_left.x,
@@ -188,21 +239,87 @@ const PART2_CUTOFF: f32 = 32.0;
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::ErrorKind;
+    use crate::generators;
 
     #[test]
     fn test_part1() {
-        let input = input_gen(include_str!("../input/tests/d6.txt"));
+        let input = input_gen(include_str!("../input/tests/d6.txt")).unwrap();
         assert_eq!(solve_part1(&input), 17);
     }
 
     #[test]
     fn test_part2() {
-        let input = input_gen(include_str!("../input/tests/d6.txt"));
+        let input = input_gen(include_str!("../input/tests/d6.txt")).unwrap();
         assert_eq!(solve_part2(&input), 16);
     }
 
+    // The bounding box used to take its right edge from the largest y
+    #[test]
+    fn test_wider_than_tall() {
+        let input = input_gen("0, 0\n12, 0\n6, 4\n0, 6\n12, 6\n9, 3\n").unwrap();
+        assert_eq!(solve_part1(&input), 12);
+    }
+
+    #[test]
+    fn test_bad_point() {
+        let err = input_gen("1, 1\n1, -6").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 4));
+        assert_eq!(input_gen("1, 1\n8").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn test_too_few_points() {
+        let err = input_gen("").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Missing("at least two coordinates"));
+        assert_eq!(input_gen("1, 1\n").unwrap_err().line, 2);
+        assert_eq!(solve_part1(&input_gen("1, 1\n1, 1").unwrap()), 0);
+    }
+
     #[test]
     fn manhattan_distance_test() {
         assert_eq!(manhattan_distance(&[0.0, 0.0], &[3.0, 10.0]), 13.0);
     }
+
+    // Checks every location in the bounding box against every point. Areas
+    // reaching the edge of the box are infinite
+    fn reference(points: &[Point]) -> (u32, usize) {
+        let distance = |p: &Point, x: u32, y: u32| {
+            (i64::from(p.x) - i64::from(x)).abs() + (i64::from(p.y) - i64::from(y)).abs()
+        };
+        let min_x = points.iter().map(|p| p.x).min().unwrap();
+        let max_x = points.iter().map(|p| p.x).max().unwrap();
+        let min_y = points.iter().map(|p| p.y).min().unwrap();
+        let max_y = points.iter().map(|p| p.y).max().unwrap();
+
+        let mut areas = vec![Some(0); points.len()];
+        let mut region = 0;
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                let distances: Vec<_> = points.iter().map(|p| distance(p, x, y)).collect();
+                if (distances.iter().sum::<i64>() as f32) < PART2_CUTOFF {
+                    region += 1;
+                }
+                let nearest = *distances.iter().min().unwrap();
+                if distances.iter().filter(|&&d| d == nearest).count() > 1 {
+                    continue;
+                }
+                let i = distances.iter().position(|&d| d == nearest).unwrap();
+                let edge = x == min_x || x == max_x || y == min_y || y == max_y;
+                areas[i] = if edge { None } else { areas[i].map(|a| a + 1) };
+            }
+        }
+
+        (areas.into_iter().flatten().max().unwrap_or(0), region)
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        generators::check(100, |rng| {
+            let points = input_gen(&generators::day6(rng)).unwrap();
+            let (largest, region) = reference(&points);
+            assert_eq!(solve_part1(&points), largest);
+            assert_eq!(solve_part2(&points), region);
+        });
+    }
 }
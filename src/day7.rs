@@ -1,10 +1,10 @@
+use crate::error::{self, ParseError};
+use crate::input;
 use min_max_heap::MinMaxHeap;
 use regex::Regex;
-use std::{
-    char::ParseCharError,
-    collections::{HashMap, VecDeque},
-    str::FromStr,
-};
+use std::{collections::HashMap, str::FromStr};
+
+const DAY: u8 = 7;
 
 lazy_static! {
     static ref regexp: Regex = Regex::new(
@@ -17,11 +17,14 @@ lazy_static! {
     .unwrap();
 }
 
+#[aoc_generator(day7)]
+pub fn input_gen(input: &str) -> Result<Graph, ParseError> {
+    input::normalize(input).parse()
+}
+
 #[aoc(day7, part1)]
-fn solve_part1(input: &str) -> String {
-    let mut g = input
-        .parse::<Graph>()
-        .expect("Failed to parse graph from str");
+pub fn solve_part1(input: &Graph) -> String {
+    let mut g = input.clone();
     let mut processing = MinMaxHeap::with_capacity(26);
     let mut steps = String::new();
 
@@ -43,32 +46,31 @@ fn solve_part1(input: &str) -> String {
 }
 
 #[aoc(day7, part2)]
-fn solve_part2(input: &str) -> u32 {
-    let mut g = input
-        .parse::<Graph>()
-        .expect("Failed to parse graph from str");
-    let mut processing = VecDeque::with_capacity(26);
+pub fn solve_part2(input: &Graph) -> u32 {
+    let mut g = input.clone();
+    let mut processing = MinMaxHeap::with_capacity(26);
     let mut total_time = 0;
 
+    // Like part 1, ready steps are started in alphabetical order
     for (s, in_degree) in &g.in_degrees {
         if *in_degree == 0 {
-            processing.push_back(*s);
+            processing.push(*s);
         }
     }
 
     let mut q = TaskQueue::new_with_worker_num(5);
 
-    while processing.len() > 0 || q.has_working() {
+    while !processing.is_empty() || q.has_working() {
         let finished = q.process();
         total_time += 1;
         for c in finished {
             for adjacent in g.remove_node(c) {
-                processing.push_back(*adjacent);
+                processing.push(*adjacent);
             }
         }
 
-        while q.has_idle() && processing.len() > 0 {
-            let step = processing.pop_front();
+        while q.has_idle() && !processing.is_empty() {
+            let step = processing.pop_min();
             q.add_task(step.unwrap());
         }
     }
@@ -76,7 +78,8 @@ fn solve_part2(input: &str) -> u32 {
     total_time - 1
 }
 
-struct Graph {
+#[derive(Clone, Debug)]
+pub struct Graph {
     adjacency_list: HashMap<char, Vec<char>>,
     in_degrees: HashMap<char, u32>,
 }
@@ -90,13 +93,10 @@ impl Graph {
     }
 
     fn add_edge(&mut self, from: char, to: char) {
-        self.adjacency_list
-            .entry(from)
-            .or_insert(Vec::new())
-            .push(to);
+        self.adjacency_list.entry(from).or_default().push(to);
         *self.in_degrees.entry(to).or_insert(0) += 1;
         self.in_degrees.entry(from).or_insert(0);
-        self.adjacency_list.entry(to).or_insert(Vec::new());
+        self.adjacency_list.entry(to).or_default();
     }
 
     fn remove_node(&mut self, node: char) -> Vec<&char> {
@@ -112,21 +112,46 @@ impl Graph {
         ready_to_process
     }
 
-    fn len(&self) -> usize {
-        self.adjacency_list.len()
+    // Whether `to` can be reached from `from` by following the edges
+    fn reaches(&self, from: char, to: char) -> bool {
+        let mut stack = vec![from];
+        let mut seen = vec![from];
+        while let Some(node) = stack.pop() {
+            if node == to {
+                return true;
+            }
+            for &next in self.adjacency_list.get(&node).into_iter().flatten() {
+                if !seen.contains(&next) {
+                    seen.push(next);
+                    stack.push(next);
+                }
+            }
+        }
+        false
     }
 }
 
 impl FromStr for Graph {
-    type Err = ParseCharError;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let mut g = Graph::new();
-        for line in s.lines() {
-            let caps = regexp.captures(line).expect("No captures found");
-            g.add_edge(caps["from"].parse().unwrap(), caps["to"].parse().unwrap());
+        for line in error::lines(DAY, s) {
+            let caps = regexp.captures(line.text()).ok_or_else(|| {
+                line.malformed("a step like `Step C must be finished before step A can begin.`")
+            })?;
+            let from = line.parse(caps.name("from").unwrap().as_str())?;
+            let to = line.parse(caps.name("to").unwrap().as_str())?;
+            // A cycle would leave its steps waiting on each other forever
+            if g.reaches(to, from) {
+                return Err(line.malformed("a step that doesn't wait on itself"));
+            }
+            g.add_edge(from, to);
         }
 
+        if g.in_degrees.is_empty() {
+            return Err(ParseError::missing(DAY, 1, "the steps"));
+        }
         Ok(g)
     }
 }
@@ -154,11 +179,8 @@ impl TaskQueue {
 
     fn has_idle(&self) -> bool {
         for w in &self.workers {
-            match w.state {
-                State::Idle => {
-                    return true;
-                }
-                _ => (),
+            if let State::Idle = w.state {
+                return true;
             }
         }
 
@@ -167,11 +189,8 @@ impl TaskQueue {
 
     fn has_working(&self) -> bool {
         for w in &self.workers {
-            match w.state {
-                State::Active { .. } => {
-                    return true;
-                }
-                _ => (),
+            if let State::Active { .. } = w.state {
+                return true;
             }
         }
 
@@ -181,12 +200,9 @@ impl TaskQueue {
     fn add_task(&mut self, task: char) -> bool {
         let mut idle_worker = None;
         for w in self.workers.iter_mut() {
-            match w.state {
-                State::Idle => {
-                    idle_worker = Some(w);
-                    break;
-                }
-                _ => (),
+            if let State::Idle = w.state {
+                idle_worker = Some(w);
+                break;
             }
         }
 
@@ -248,18 +264,61 @@ impl Worker {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::ErrorKind;
+    use crate::generators;
+    use std::collections::BTreeSet;
 
     #[test]
     fn test_part1() {
-        let input = include_str!("../input/tests/d7.txt");
+        let input = input_gen(include_str!("../input/tests/d7.txt")).unwrap();
         assert_eq!(&solve_part1(&input), "CABDFE");
     }
 
+    // Ready steps used to start in the order they became ready, so Z went
+    // before F here and F had to wait for B to finish instead
+    #[test]
+    fn test_part2_alphabetical() {
+        let input: Vec<_> = "ZBCDEF"
+            .chars()
+            .map(|s| format!("Step A must be finished before step {} can begin.", s))
+            .collect();
+        let input = input_gen(&input.join("\n")).unwrap();
+        assert_eq!(solve_part2(&input), 61 + 62 + 86);
+    }
+
+    #[test]
+    fn test_bad_step() {
+        let input = "Step C must be finished before step A can begin.\nStep C must finish";
+        let err = input_gen(input).unwrap_err();
+        assert_eq!((err.day, err.line), (7, 2));
+    }
+
+    #[test]
+    fn test_cycle() {
+        let step = |from, to| {
+            format!(
+                "Step {} must be finished before step {} can begin.",
+                from, to
+            )
+        };
+        let input = [step('A', 'B'), step('B', 'C'), step('C', 'A')].join("\n");
+        let err = input_gen(&input).unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(
+            err.kind,
+            ErrorKind::Malformed("a step that doesn't wait on itself")
+        );
+        assert_eq!(input_gen(&step('Q', 'Q')).unwrap_err().line, 1);
+        assert!(input_gen(&[step('A', 'B'), step('A', 'C'), step('B', 'C')].join("\n")).is_ok());
+
+        let err = input_gen("\n").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Missing("the steps"));
+    }
+
     #[test]
     fn test_regex() {
         let line = "Step G must be finished before step T can begin.";
         let caps = regexp.captures(line).expect("Found no captures");
-        let (from, to) = ("from".to_owned(), "to".to_owned());
         let formatted = format!(
             "FROM: {} TO: {}",
             caps["from"].parse::<char>().unwrap(),
@@ -273,4 +332,63 @@ mod test {
         assert_eq!(task_time('A'), 61);
         assert_eq!(task_time('Z'), 86);
     }
+
+    // The order steps are done in, along with how long it takes five workers
+    // to do them all. Whenever several steps are ready the first one in
+    // alphabetical order goes first
+    fn reference(input: &str) -> (String, u32) {
+        let edges: Vec<(char, char)> = input
+            .lines()
+            .map(|l| {
+                let words: Vec<&str> = l.split_whitespace().collect();
+                (words[1].parse().unwrap(), words[7].parse().unwrap())
+            })
+            .collect();
+        let steps: BTreeSet<char> = edges.iter().flat_map(|&(a, b)| vec![a, b]).collect();
+        let ready = |done: &BTreeSet<char>, started: &BTreeSet<char>| {
+            steps
+                .iter()
+                .cloned()
+                .filter(|s| !started.contains(s))
+                .filter(|s| edges.iter().all(|(a, b)| b != s || done.contains(a)))
+                .collect::<Vec<_>>()
+        };
+
+        let mut order = String::new();
+        let mut done = BTreeSet::new();
+        while let Some(&step) = ready(&done, &done).first() {
+            order.push(step);
+            done.insert(step);
+        }
+
+        let (mut done, mut started) = (BTreeSet::new(), BTreeSet::new());
+        let (mut working, mut time) = (Vec::new(), 0);
+        loop {
+            for step in ready(&done, &started) {
+                if working.len() < 5 {
+                    started.insert(step);
+                    working.push((step, time + u32::from(task_time(step))));
+                }
+            }
+            match working.iter().map(|&(_, end)| end).min() {
+                Some(end) => time = end,
+                None => return (order, time),
+            }
+            for &(step, _) in working.iter().filter(|&&(_, end)| end == time) {
+                done.insert(step);
+            }
+            working.retain(|&(_, end)| end != time);
+        }
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        generators::check(200, |rng| {
+            let input = generators::day7(rng);
+            let graph = input_gen(&input).unwrap();
+            let (order, time) = reference(&input);
+            assert_eq!(solve_part1(&graph), order);
+            assert_eq!(solve_part2(&graph), time);
+        });
+    }
 }
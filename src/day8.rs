@@ -1,90 +1,373 @@
-use std::collections::HashMap;
+use crate::error::{ErrorKind, ParseError, ReadError};
+use crate::input;
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::io::BufRead;
 
+const DAY: u8 = 8;
+
+// Only a list that holds exactly one whole tree is accepted
 #[aoc_generator(day8)]
-fn input_gen(input: &str) -> Vec<u32> {
-    input
-        .split_whitespace()
-        .map(|s| s.parse().unwrap())
-        .collect()
+pub fn input_gen(input: &str) -> Result<Vec<u32>, ParseError> {
+    let input = &input::normalize(input);
+    let mut list = Vec::new();
+    read_tree(input.as_bytes(), |n| list.push(n)).map_err(ReadError::into_parse)?;
+    Ok(list)
 }
 
 #[aoc(day8, part1)]
-fn solve_part1(list: &[u32]) -> u32 {
-    let mut sum = 0;
-    metadata_sum(list, &mut sum, 0);
-    sum
+pub fn solve_part1(list: &[u32]) -> Result<u64, TreeError> {
+    let (sum, _) = walk_list(list).ok_or(TreeError::NotOneTree)?;
+    sum.ok_or(TreeError::Overflow)
 }
 
 #[aoc(day8, part2)]
-fn solve_part2(list: &[u32]) -> u32 {
-    root_value(list, 0).1
+pub fn solve_part2(list: &[u32]) -> Result<u64, TreeError> {
+    let (_, value) = walk_list(list).ok_or(TreeError::NotOneTree)?;
+    value.ok_or(TreeError::Overflow)
 }
 
-fn metadata_sum(data: &[u32], sum: &mut u32, root: usize) -> usize {
-    let mut child_count = data[root];
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeError {
+    // Ends early or has numbers after the root, which `input_gen` checks
+    NotOneTree,
+    Overflow,
+}
 
-    let mut curr = root + 2;
-    
-    while child_count > 0 {
-        curr = metadata_sum(data, sum, curr);
-        child_count -= 1;
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TreeError::NotOneTree => write!(f, "the list isn't exactly one tree"),
+            TreeError::Overflow => write!(f, "the total is too big to hold"),
+        }
     }
+}
 
-    let metadata_end = curr + (data[root + 1] as usize);
-    for m in curr..metadata_end {
-        *sum += data[m];
-    }
+impl Error for TreeError {}
+
+// `None` if the total is too big to hold
+pub fn solve_part1_from<R: BufRead>(reader: R) -> Result<Option<u64>, ReadError> {
+    Ok(solve_from(reader)?.0)
+}
+
+pub fn solve_part2_from<R: BufRead>(reader: R) -> Result<Option<u64>, ReadError> {
+    Ok(solve_from(reader)?.1)
+}
 
-    return metadata_end;
+// Both parts straight from the reader, a number at a time
+pub fn solve_from<R: BufRead>(reader: R) -> Result<Totals, ReadError> {
+    read_tree(reader, |_| ())
 }
 
-fn root_value(data: &[u32], root: usize) -> (usize, u32) {
-    let mut child_count = data[root];
+// Walks the tree, handing `keep` every number on the way, and checks that
+// nothing comes after it
+fn read_tree<R: BufRead, F: FnMut(u32)>(reader: R, mut keep: F) -> Result<Totals, ReadError> {
+    let mut numbers = Numbers::new(reader);
+    let tree = walk(numbers.by_ref().inspect(|n| {
+        if let Ok(n) = n {
+            keep(*n);
+        }
+    }))?
+    .ok_or_else(|| ParseError::missing(DAY, numbers.start.0, "the rest of the tree"))?;
 
-    let mut curr = root + 2;
-    let mut value_map = HashMap::new();
-    for i in 1..=child_count {
-        let res = root_value(data, curr);
-        curr = res.0;
-        value_map.insert(i, res.1);
+    match numbers.next().transpose()? {
+        Some(extra) => {
+            let (line, column) = numbers.start;
+            let expected = ErrorKind::Malformed("nothing after the root node");
+            Err(ParseError::new(DAY, line, column, &extra.to_string(), expected).into())
+        }
+        None => Ok(tree),
     }
+}
+
+fn walk_list(list: &[u32]) -> Option<Totals> {
+    let mut numbers = list.iter().map(|&n| Ok::<_, Infallible>(n));
+    let Ok(tree) = walk(numbers.by_ref());
+    tree.filter(|_| numbers.next().is_none())
+}
+
+// A node whose metadata hasn't been read yet
+struct Node {
+    children: usize,
+    metadata: u32,
+    values: Vec<Option<u64>>,
+}
+
+// The sum of all the metadata and the root's value, each `None` if it's
+// too big to hold
+pub type Totals = (Option<u64>, Option<u64>);
+
+// Reads the tree in order with a stack of the nodes still open, so that
+// neither the numbers nor the depth of the tree are limited by memory or
+// the call stack. Returns the totals, or `None` if the numbers ran out first
+fn walk<E, I>(mut numbers: I) -> Result<Option<Totals>, E>
+where
+    I: Iterator<Item = Result<u32, E>>,
+{
+    let mut next = || numbers.next().transpose();
+    let mut stack: Vec<Node> = Vec::new();
+    let mut sum = Some(0u64);
+    loop {
+        let (Some(children), Some(metadata)) = (next()?, next()?) else {
+            return Ok(None);
+        };
+        stack.push(Node {
+            children: children as usize,
+            metadata,
+            values: Vec::new(),
+        });
 
-    let metadata_end = curr + (data[root + 1] as usize);
-    let value = match child_count {
-        0 => {
-            let mut sum = 0;
-            for m in curr..metadata_end {
-                sum += data[m];
+        // Close every node that has all its children
+        while stack.last().is_some_and(|n| n.values.len() == n.children) {
+            let node = stack.pop().unwrap();
+            // A child's value only overflows its parent's if it's counted
+            let mut value = Some(0u64);
+            for _ in 0..node.metadata {
+                let Some(m) = next()? else {
+                    return Ok(None);
+                };
+                sum = sum.and_then(|s| s.checked_add(u64::from(m)));
+                let counted = match node.children {
+                    0 => Some(u64::from(m)),
+                    _ => (m as usize)
+                        .checked_sub(1)
+                        .and_then(|i| node.values.get(i))
+                        .cloned()
+                        .unwrap_or(Some(0)),
+                };
+                value = value.zip(counted).and_then(|(v, c)| v.checked_add(c));
             }
 
-            sum
+            match stack.last_mut() {
+                Some(parent) => parent.values.push(value),
+                None => return Ok(Some((sum, value))),
+            }
         }
-        _ => {
-            let mut sum = 0;
-            for m in curr..metadata_end {
-                sum += *value_map.entry(data[m]).or_insert(0);
+    }
+}
+
+// The numbers in a license file, read a chunk at a time rather than a
+// line, the whole tree is usually on one
+struct Numbers<R> {
+    reader: R,
+    line: usize,
+    column: usize,
+    // Where the last number read started
+    start: (usize, usize),
+    // How much of a byte order mark has been skipped, until something else is read
+    bom: Option<usize>,
+}
+
+const BOM: &[u8] = "\u{feff}".as_bytes();
+
+impl<R: BufRead> Numbers<R> {
+    fn new(reader: R) -> Self {
+        Numbers {
+            reader,
+            line: 1,
+            column: 1,
+            start: (1, 1),
+            bom: Some(0),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Numbers<R> {
+    type Item = Result<u32, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut token = Vec::new();
+        let mut start = (self.line, self.column);
+        let mut done = false;
+        while !done {
+            let buf = match self.reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if buf.is_empty() {
+                break;
             }
 
-            sum
+            let mut read = 0;
+            for &b in buf {
+                read += 1;
+                if let Some(i) = self.bom {
+                    if BOM.get(i) == Some(&b) {
+                        self.bom = Some(i + 1);
+                        continue;
+                    }
+                    self.bom = None;
+                }
+
+                if b.is_ascii_whitespace() {
+                    if b == b'\n' {
+                        self.line += 1;
+                        self.column = 1;
+                    } else {
+                        self.column += 1;
+                    }
+                    if !token.is_empty() {
+                        done = true;
+                        break;
+                    }
+                } else {
+                    if token.is_empty() {
+                        start = (self.line, self.column);
+                    }
+                    token.push(b);
+                    // Columns count chars, not the bytes they're encoded in
+                    if b & 0xc0 != 0x80 {
+                        self.column += 1;
+                    }
+                }
+            }
+            self.reader.consume(read);
         }
-    };
 
-    (metadata_end, value)
+        if token.is_empty() {
+            return None;
+        }
+        self.start = start;
+        let text = String::from_utf8_lossy(&token);
+        Some(text.parse().map_err(|_| {
+            let (line, column) = start;
+            ParseError::new(DAY, line, column, &text, ErrorKind::InvalidNumber).into()
+        }))
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::generators;
+    use std::io::BufReader;
 
     #[test]
     fn test_part1() {
-        let input = input_gen("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2");
-        assert_eq!(solve_part1(&input), 138);
+        let input = input_gen("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        assert_eq!(solve_part1(&input), Ok(138));
     }
 
     #[test]
     fn test_part2() {
-        let input = input_gen("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2");
-        assert_eq!(solve_part2(&input), 66);
+        let input = input_gen("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        assert_eq!(solve_part2(&input), Ok(66));
+    }
+
+    #[test]
+    fn test_bad_entry() {
+        let err = input_gen("2 3 0 3 10\n11 1a 1 1").unwrap_err();
+        assert_eq!((err.line, err.column, err.text.as_str()), (2, 4, "1a"));
+    }
+
+    #[test]
+    fn test_incomplete_tree() {
+        let err = input_gen("1 1 0 1").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Missing("the rest of the tree"));
+        assert_eq!(solve_part1(&[1, 1, 0, 1]), Err(TreeError::NotOneTree));
+
+        let err = input_gen("0 1 5\n 7 7 7").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ErrorKind::Malformed("nothing after the root node")
+        );
+        assert_eq!((err.line, err.column, err.text.as_str()), (2, 2, "7"));
+        assert!(solve_from("0 1 5 7 7 7".as_bytes()).is_err());
+        assert_eq!(solve_part2(&[0, 1, 5, 7]), Err(TreeError::NotOneTree));
+        assert_eq!(solve_part2(&[0, 1, 5]), Ok(5));
+    }
+
+    // Each node's value is twice its child's, and used to overflow a u32
+    #[test]
+    fn test_overflow() {
+        let doubling = |depth| "1 2 ".repeat(depth) + "0 1 5" + &" 1 1".repeat(depth);
+        let list = input_gen(&doubling(40)).unwrap();
+        assert_eq!(solve_part1(&list), Ok(5 + 80));
+        assert_eq!(solve_part2(&list), Ok(5 << 40));
+
+        let list = input_gen(&doubling(64)).unwrap();
+        assert_eq!(solve_part1(&list), Ok(5 + 128));
+        assert_eq!(solve_part2(&list), Err(TreeError::Overflow));
+        assert_eq!(
+            solve_from(doubling(64).as_bytes()).unwrap(),
+            (Some(133), None)
+        );
+
+        // An overflowing child that isn't counted doesn't matter
+        let text = "2 1 ".to_string() + &doubling(64) + " 0 1 7 2";
+        assert_eq!(solve_part2(&input_gen(&text).unwrap()), Ok(7));
+    }
+
+    struct Node {
+        children: Vec<Node>,
+        metadata: Vec<u64>,
+    }
+
+    impl Node {
+        fn read<I: Iterator<Item = u32>>(numbers: &mut I) -> Node {
+            let (children, metadata) = (numbers.next().unwrap(), numbers.next().unwrap());
+            Node {
+                children: (0..children).map(|_| Node::read(numbers)).collect(),
+                metadata: numbers.take(metadata as usize).map(u64::from).collect(),
+            }
+        }
+
+        fn sum(&self) -> u64 {
+            self.metadata.iter().sum::<u64>() + self.children.iter().map(Node::sum).sum::<u64>()
+        }
+
+        fn value(&self) -> u64 {
+            if self.children.is_empty() {
+                return self.sum();
+            }
+            self.metadata
+                .iter()
+                .filter_map(|&m| self.children.get((m as usize).checked_sub(1)?))
+                .map(Node::value)
+                .sum()
+        }
+    }
+
+    #[test]
+    fn test_random_inputs() {
+        generators::check(100, |rng| {
+            let list = input_gen(&generators::day8(rng)).unwrap();
+            let root = Node::read(&mut list.iter().cloned());
+            assert_eq!(solve_part1(&list), Ok(root.sum()));
+            assert_eq!(solve_part2(&list), Ok(root.value()));
+
+            let text = generators::day8(rng);
+            let list = input_gen(&text).unwrap();
+            let reader = BufReader::with_capacity(1 + rng.below(4), text.as_bytes());
+            assert_eq!(
+                solve_from(reader).unwrap(),
+                (solve_part1(&list).ok(), solve_part2(&list).ok())
+            );
+        });
+    }
+
+    #[test]
+    fn test_read_stream() {
+        let text = "\u{feff}2 3 0 3 10\r\n 11 12 1 1 0 1 99 2\n1 1 2\n";
+        let reader = BufReader::with_capacity(2, text.as_bytes());
+        assert_eq!(solve_from(reader).unwrap(), (Some(138), Some(66)));
+
+        match solve_part1_from("2 3 0 3 10\n11 12 1 1 0 1\n99".as_bytes()) {
+            Err(ReadError::Parse(err)) => {
+                assert_eq!(err.kind, ErrorKind::Missing("the rest of the tree"));
+                assert_eq!(err.line, 3);
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+
+        // Far deeper than the call stack would allow
+        let depth = 1_000_000;
+        let text = "1 1 ".repeat(depth) + "0 1 5" + &" 1".repeat(depth);
+        let totals = (Some(5 + depth as u64), Some(5));
+        assert_eq!(solve_from(text.as_bytes()).unwrap(), totals);
+
+        let err = input_gen("\u{feff}2 3 0 é1").unwrap_err();
+        assert_eq!((err.line, err.column, err.text.as_str()), (1, 7, "é1"));
     }
 }
@@ -1,70 +1,62 @@
-use std::{
-    char::ParseCharError,
-    collections::{HashMap, VecDeque},
-    str::FromStr,
-};
+use crate::error::{self, ParseError};
+use crate::input;
+use regex::Regex;
+use std::collections::HashMap;
 
-const PLAYERS: usize = 426;
-const LAST_MARBLE_VALUE: usize = 72058;
+const DAY: u8 = 9;
 
-#[aoc(day9, part1)]
-fn solve_part1(_: &str) -> usize {
-    winning_score_fast(PLAYERS, LAST_MARBLE_VALUE)
+lazy_static! {
+    static ref regexp: Regex = Regex::new(
+        r"^(?P<players>[0-9]+) players; last marble is worth (?P<last_marble>[0-9]+) points$"
+    )
+    .unwrap();
 }
 
-#[aoc(day9, part2)]
-fn solve_part2(_: &str) -> usize {
-    winning_score_fast(PLAYERS, LAST_MARBLE_VALUE * 100)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Setup {
+    pub players: usize,
+    pub last_marble: usize,
 }
 
-// This was way too inefficient for part 2 (Ran for over 10 minutes without returning an answer!)
-// Arbitrary inserts and removals with arrays is not ideal
-fn winning_score(num_players: usize, last_marble: usize) -> usize {
-    let mut player_scores = HashMap::new();
-    let mut game_board = vec![0, 1];
-    let (mut curr_marble, mut player): (usize, usize) = (1, 1);
-
-    for m in 2..=last_marble {
-        match m % 23 {
-            0 => {
-                let player_score = player_scores.entry(player).or_insert(0);
-                *player_score += m;
-                curr_marble = ((curr_marble + game_board.len()).wrapping_sub(7)) % game_board.len();
-                *player_score += game_board.remove(curr_marble);
-                curr_marble %= game_board.len();
-            }
-            _ => {
-                curr_marble = (curr_marble + 2) % game_board.len();
-                if curr_marble == 0 {
-                    game_board.push(m);
-                    curr_marble = game_board.len() - 1;
-                } else {
-                    game_board.insert(curr_marble, m);
-                }
-            }
-        }
-
-        player += 1;
-        player %= num_players;
+#[aoc_generator(day9)]
+pub fn input_gen(input: &str) -> Result<Setup, ParseError> {
+    let input = &input::normalize(input);
+    let line = error::lines(DAY, input)
+        .next()
+        .ok_or_else(|| ParseError::missing(DAY, 1, "the number of players"))?;
+    let expected = "a game like `10 players; last marble is worth 1618 points`";
+    let caps = regexp
+        .captures(line.text())
+        .ok_or_else(|| line.malformed(expected))?;
+    let setup = Setup {
+        players: line.parse(caps.name("players").unwrap().as_str())?,
+        last_marble: line.parse(caps.name("last_marble").unwrap().as_str())?,
+    };
+    if setup.players == 0 {
+        return Err(line.malformed("at least one player"));
     }
+    Ok(setup)
+}
+
+#[aoc(day9, part1)]
+pub fn solve_part1(setup: &Setup) -> usize {
+    winning_score_fast(setup.players, setup.last_marble)
+}
 
-    *player_scores.iter().map(|(p, score)| score).max().unwrap()
+#[aoc(day9, part2)]
+pub fn solve_part2(setup: &Setup) -> usize {
+    winning_score_fast(setup.players, setup.last_marble * 100)
 }
 
-// This passes all but one test case but gave the correct answer for pt 1 & 2 ...
 fn winning_score_fast(num_players: usize, last_marble: usize) -> usize {
     let mut game = Game::new(num_players);
 
-    for m in 2..last_marble {
+    for m in 2..=last_marble {
         game.turn(m);
     }
 
-    *game
-        .player_scores
-        .iter()
-        .map(|(p, score)| score)
-        .max()
-        .unwrap()
+    // Nobody scores before the 23rd marble
+    game.player_scores.values().max().cloned().unwrap_or(0)
 }
 
 struct Game {
@@ -173,6 +165,50 @@ impl Marble {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::error::ErrorKind;
+    use crate::generators;
+
+    // The game played out on a `Vec`, the way part 1 was first solved. Far
+    // too slow for part 2, with every insert and remove shifting marbles
+    fn winning_score_slow(num_players: usize, last_marble: usize) -> usize {
+        let mut player_scores = HashMap::new();
+        let mut game_board = vec![0, 1];
+        let (mut curr_marble, mut player): (usize, usize) = (1, 1);
+
+        for m in 2..=last_marble {
+            match m % 23 {
+                0 => {
+                    let player_score = player_scores.entry(player).or_insert(0);
+                    *player_score += m;
+                    curr_marble =
+                        ((curr_marble + game_board.len()).wrapping_sub(7)) % game_board.len();
+                    *player_score += game_board.remove(curr_marble);
+                    curr_marble %= game_board.len();
+                }
+                _ => {
+                    curr_marble = (curr_marble + 2) % game_board.len();
+                    if curr_marble == 0 {
+                        game_board.push(m);
+                        curr_marble = game_board.len() - 1;
+                    } else {
+                        game_board.insert(curr_marble, m);
+                    }
+                }
+            }
+
+            player += 1;
+            player %= num_players;
+        }
+
+        *player_scores.values().max().unwrap()
+    }
+
+    #[test]
+    fn test_winning_score_slow() {
+        assert_eq!(winning_score_slow(9, 25), 32);
+        assert_eq!(winning_score_slow(10, 1618), 8317);
+        assert_eq!(winning_score_slow(17, 1104), 2764);
+    }
 
     #[test]
     fn test_winning_score() {
@@ -182,4 +218,47 @@ mod test {
         assert_eq!(winning_score_fast(21, 6111), 54718);
         assert_eq!(winning_score_fast(30, 5807), 37305);
     }
+
+    // The last marble used to be left out, so a game ending on a scoring
+    // marble lost its score
+    #[test]
+    fn test_last_marble() {
+        assert_eq!(winning_score_fast(9, 23), 32);
+        assert_eq!(winning_score_fast(9, 46), 63);
+    }
+
+    #[test]
+    fn test_random_games() {
+        generators::check(50, |rng| {
+            let setup = input_gen(&generators::day9(rng)).unwrap();
+            assert_eq!(
+                solve_part1(&setup),
+                winning_score_slow(setup.players, setup.last_marble)
+            );
+        });
+    }
+
+    #[test]
+    fn test_input() {
+        let setup = input_gen("10 players; last marble is worth 1618 points\n").unwrap();
+        assert_eq!(
+            setup,
+            Setup {
+                players: 10,
+                last_marble: 1618
+            }
+        );
+        assert_eq!(solve_part1(&setup), 8317);
+        let setup = input_gen("9 players; last marble is worth 22 points").unwrap();
+        assert_eq!(solve_part1(&setup), 0);
+
+        let err = input_gen("").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Missing("the number of players"));
+        let err = input_gen("0 players; last marble is worth 25 points").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::Malformed("at least one player"));
+        let err = input_gen("10 players; last marble is worth lots of points").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+        let err = input_gen("99999999999999999999 players; last marble is worth 1 points");
+        assert_eq!(err.unwrap_err().kind, ErrorKind::InvalidNumber);
+    }
 }
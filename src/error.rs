@@ -0,0 +1,232 @@
+use std::{error::Error, fmt, io, str::FromStr};
+
+// Every generator reports bad input through this, pointing at the
+// day, line (1-based) and column (1-based, in chars) of the offending text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub day: u8,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    // The line doesn't have the layout the day expects, holds a description of it
+    Malformed(&'static str),
+    InvalidNumber,
+    UnexpectedChar(char),
+    // The input ended before the named section
+    Missing(&'static str),
+}
+
+impl ParseError {
+    pub fn new(day: u8, line: usize, column: usize, text: &str, kind: ErrorKind) -> Self {
+        ParseError {
+            day,
+            line,
+            column,
+            text: text.to_owned(),
+            kind,
+        }
+    }
+
+    pub fn missing(day: u8, line: usize, section: &'static str) -> Self {
+        ParseError::new(day, line, 1, "", ErrorKind::Missing(section))
+    }
+
+    // Errors from a `FromStr` impl are relative to the string it was given,
+    // this moves them to where that string starts in the whole input
+    pub fn offset(mut self, lines: usize) -> Self {
+        self.line += lines;
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "day {}, line {}, column {}: ",
+            self.day, self.line, self.column
+        )?;
+        match self.kind {
+            ErrorKind::Malformed(expected) => {
+                write!(f, "expected {}, found `{}`", expected, self.text)
+            }
+            ErrorKind::InvalidNumber => write!(f, "invalid number `{}`", self.text),
+            ErrorKind::UnexpectedChar(c) => write!(f, "unexpected character `{}`", c),
+            ErrorKind::Missing(section) => write!(f, "missing {}", section),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+// A part that has no answer for input that was read fine, like a search
+// that would never end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolveError {
+    pub day: u8,
+    pub part: u8,
+    pub reason: String,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "day {}, part {}: {}", self.day, self.part, self.reason)
+    }
+}
+
+impl Error for SolveError {}
+
+// What can go wrong reading input from a stream rather than a `&str`
+#[derive(Debug)]
+pub enum ReadError {
+    Parse(ParseError),
+    Io(io::Error),
+}
+
+impl ReadError {
+    // For the `&str` wrappers, reading from memory can only fail to parse
+    pub fn into_parse(self) -> ParseError {
+        match self {
+            ReadError::Parse(e) => e,
+            ReadError::Io(e) => panic!("reading from memory failed: {}", e),
+        }
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Parse(e) => e.fmt(f),
+            ReadError::Io(e) => write!(f, "could not read input: {}", e),
+        }
+    }
+}
+
+impl Error for ReadError {}
+
+impl From<ParseError> for ReadError {
+    fn from(e: ParseError) -> Self {
+        ReadError::Parse(e)
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+// A line of puzzle input that knows where it came from, so that
+// errors about it (or any slice of it) can be located
+#[derive(Debug, Clone, Copy)]
+pub struct Line<'a> {
+    day: u8,
+    number: usize,
+    text: &'a str,
+}
+
+pub fn lines(day: u8, input: &str) -> impl Iterator<Item = Line<'_>> {
+    input
+        .lines()
+        .enumerate()
+        .map(move |(i, text)| Line::new(day, i + 1, text))
+}
+
+impl<'a> Line<'a> {
+    pub fn new(day: u8, number: usize, text: &'a str) -> Self {
+        Line { day, number, text }
+    }
+
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    // Column of `field`, which should be a slice of this line's text
+    pub fn column(&self, field: &str) -> usize {
+        let start = self.text.as_ptr() as usize;
+        let offset = (field.as_ptr() as usize).wrapping_sub(start);
+        if offset <= self.text.len() {
+            self.text[..offset].chars().count() + 1
+        } else {
+            1
+        }
+    }
+
+    pub fn parse<T: FromStr>(&self, field: &str) -> Result<T, ParseError> {
+        field.parse().map_err(|_| {
+            ParseError::new(
+                self.day,
+                self.number,
+                self.column(field),
+                field,
+                ErrorKind::InvalidNumber,
+            )
+        })
+    }
+
+    // Parses the whole line with a `FromStr` that reports its own errors
+    pub fn parse_as<T: FromStr<Err = ParseError>>(&self) -> Result<T, ParseError> {
+        self.text
+            .parse()
+            .map_err(|e: ParseError| e.offset(self.number - 1))
+    }
+
+    pub fn malformed(&self, expected: &'static str) -> ParseError {
+        ParseError::new(
+            self.day,
+            self.number,
+            1,
+            self.text,
+            ErrorKind::Malformed(expected),
+        )
+    }
+
+    // `index` is the byte index of `found` in the line
+    pub fn unexpected_char(&self, index: usize, found: char) -> ParseError {
+        ParseError::new(
+            self.day,
+            self.number,
+            self.text[..index].chars().count() + 1,
+            &found.to_string(),
+            ErrorKind::UnexpectedChar(found),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_locations() {
+        let input = "12\n3x4 56";
+        let line = lines(1, input).nth(1).unwrap();
+        let field = line.text().split(' ').next().unwrap();
+
+        let err = line.parse::<u32>(field).unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        assert_eq!(
+            err.to_string(),
+            "day 1, line 2, column 1: invalid number `3x4`"
+        );
+
+        let err = line.unexpected_char(1, 'x');
+        assert_eq!((err.line, err.column), (2, 2));
+        assert_eq!(line.parse::<u32>(&line.text()[4..]), Ok(56));
+    }
+
+    #[test]
+    fn test_offset() {
+        let err = Line::new(6, 1, "1, a").parse::<u32>("a").unwrap_err();
+        assert_eq!(err.offset(4).line, 5);
+    }
+}
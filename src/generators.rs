@@ -0,0 +1,658 @@
+// Random, valid puzzle inputs for every day, used to cross-check the
+// solvers against simpler reference implementations. Inputs are kept
+// small so that brute force references stay quick
+use crate::grid::{Grid, Point};
+use std::collections::HashSet;
+use std::thread;
+
+// splitmix64, good enough for test inputs and keeps them reproducible
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // A number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "can't pick below 0");
+        (self.next_u64() % n as u64) as usize
+    }
+
+    // A number in `low..=high`
+    pub fn between(&mut self, low: i64, high: i64) -> i64 {
+        low + self.below((high - low + 1) as usize) as i64
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64 <= probability
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
+
+// Runs `test` against `cases` seeded generators. A failing case prints its
+// seed, so it can be replayed with `Rng::new(seed)`
+pub fn check<F: FnMut(&mut Rng)>(cases: u64, mut test: F) {
+    struct Seed(u64);
+    impl Drop for Seed {
+        fn drop(&mut self) {
+            if thread::panicking() {
+                eprintln!("failed with seed {}", self.0);
+            }
+        }
+    }
+
+    for seed in 0..cases {
+        let guard = Seed(seed);
+        test(&mut Rng::new(seed));
+        drop(guard);
+    }
+}
+
+// Frequency changes, half the time adding up to 0 so that part 2 finds a
+// repeat within the first pass
+pub fn day1(rng: &mut Rng) -> String {
+    let mut changes: Vec<i64> = (0..rng.between(1, 20))
+        .map(|_| rng.between(1, 20) * if rng.chance(0.5) { 1 } else { -1 })
+        .collect();
+    // Without a change to cancel the drift the first repeat can take
+    // many times round the list, or never come
+    let drift: i64 = changes.iter().sum();
+    if drift != 0 && rng.chance(0.5) {
+        changes.push(-drift);
+    }
+
+    lines(changes.iter().map(|c| format!("{:+}", c)))
+}
+
+// Box IDs from a small alphabet, so letters repeat, with exactly one pair
+// that differs by a single letter
+pub fn day2(rng: &mut Rng) -> String {
+    let len = rng.between(4, 10) as usize;
+    let letter = |rng: &mut Rng| (b'a' + rng.below(6) as u8) as char;
+    loop {
+        let mut ids: Vec<String> = (0..rng.between(2, 20))
+            .map(|_| (0..len).map(|_| letter(rng)).collect())
+            .collect();
+        let mut near: Vec<char> = ids[0].chars().collect();
+        let i = rng.below(len);
+        while near[i] == ids[0].as_bytes()[i] as char {
+            near[i] = letter(rng);
+        }
+        ids.push(near.into_iter().collect());
+        rng.shuffle(&mut ids);
+
+        let mut pairs = 0;
+        for (i, a) in ids.iter().enumerate() {
+            for b in &ids[i + 1..] {
+                if a.chars().zip(b.chars()).filter(|(x, y)| x != y).count() <= 1 {
+                    pairs += 1;
+                }
+            }
+        }
+        if pairs == 1 {
+            return lines(ids.into_iter());
+        }
+    }
+}
+
+// Claims crowded onto a small patch of fabric so that plenty overlap
+pub fn day3(rng: &mut Rng) -> String {
+    lines((1..=rng.between(1, 30)).map(|id| {
+        format!(
+            "#{} @ {},{}: {}x{}",
+            id,
+            rng.between(0, 40),
+            rng.between(0, 40),
+            rng.between(1, 10),
+            rng.between(1, 10)
+        )
+    }))
+}
+
+// Shuffled guard logs. Shifts start just before or after midnight and
+// every nap is within the midnight hour
+pub fn day4(rng: &mut Rng) -> String {
+    let guards: Vec<i64> = (0..rng.between(1, 5))
+        .map(|_| rng.between(1, 3000))
+        .collect();
+    let mut dates = HashSet::new();
+    for _ in 0..rng.between(1, 20) {
+        // Day 1 is skipped so that the day before a shift is in the same month
+        dates.insert((rng.between(1, 12), rng.between(2, 28)));
+    }
+
+    // The first shift always has a nap, so that somebody sleeps
+    let mut records = Vec::new();
+    for (i, (month, day)) in dates.into_iter().enumerate() {
+        let guard = rng.pick(&guards);
+        let start = if rng.chance(0.5) {
+            format!(
+                "[1518-{:02}-{:02} 23:{:02}]",
+                month,
+                day - 1,
+                rng.between(45, 59)
+            )
+        } else {
+            format!("[1518-{:02}-{:02} 00:00]", month, day)
+        };
+        records.push(format!("{} Guard #{} begins shift", start, guard));
+
+        let mut minutes: Vec<i64> = (1..60).collect();
+        rng.shuffle(&mut minutes);
+        let mut minutes =
+            minutes[..2 * rng.between(if i == 0 { 1 } else { 0 }, 3) as usize].to_vec();
+        minutes.sort_unstable();
+        for nap in minutes.chunks(2) {
+            records.push(format!(
+                "[1518-{:02}-{:02} 00:{:02}] falls asleep",
+                month, day, nap[0]
+            ));
+            records.push(format!(
+                "[1518-{:02}-{:02} 00:{:02}] wakes up",
+                month, day, nap[1]
+            ));
+        }
+    }
+    rng.shuffle(&mut records);
+
+    lines(records.into_iter())
+}
+
+// A polymer from a handful of unit types, often followed by their
+// opposite so that chains of reactions happen
+pub fn day5(rng: &mut Rng) -> String {
+    let mut polymer = String::new();
+    for _ in 0..rng.between(0, 60) {
+        let unit = match polymer.chars().last() {
+            Some(last) if rng.chance(0.3) => opposite(last),
+            _ => {
+                let unit = (b'a' + rng.below(5) as u8) as char;
+                if rng.chance(0.5) {
+                    unit.to_ascii_uppercase()
+                } else {
+                    unit
+                }
+            }
+        };
+        polymer.push(unit);
+    }
+
+    polymer + "\n"
+}
+
+fn opposite(unit: char) -> char {
+    if unit.is_ascii_lowercase() {
+        unit.to_ascii_uppercase()
+    } else {
+        unit.to_ascii_lowercase()
+    }
+}
+
+// Distinct coordinates in a small square
+pub fn day6(rng: &mut Rng) -> String {
+    let mut points = Vec::new();
+    while points.len() < 2 || (points.len() < 10 && rng.chance(0.8)) {
+        let p = (rng.between(0, 30), rng.between(0, 30));
+        if !points.contains(&p) {
+            points.push(p);
+        }
+    }
+
+    lines(points.into_iter().map(|(x, y)| format!("{}, {}", x, y)))
+}
+
+// Step instructions forming a DAG, every step has at least one edge
+pub fn day7(rng: &mut Rng) -> String {
+    let mut steps: Vec<char> = (b'A'..=b'Z').map(char::from).collect();
+    rng.shuffle(&mut steps);
+    steps.truncate(rng.between(2, 10) as usize);
+
+    // Edges only go forward through the shuffled order, so there are no cycles
+    let mut edges = Vec::new();
+    for i in 0..steps.len() {
+        for j in i + 1..steps.len() {
+            if rng.chance(0.3) {
+                edges.push((steps[i], steps[j]));
+            }
+        }
+    }
+    for (i, &step) in steps.iter().enumerate() {
+        if !edges.iter().any(|&(a, b)| a == step || b == step) {
+            let other = loop {
+                let j = rng.below(steps.len());
+                if j != i {
+                    break j;
+                }
+            };
+            edges.push((steps[i.min(other)], steps[i.max(other)]));
+        }
+    }
+    rng.shuffle(&mut edges);
+
+    lines(
+        edges
+            .into_iter()
+            .map(|(a, b)| format!("Step {} must be finished before step {} can begin.", a, b)),
+    )
+}
+
+// A license tree. Metadata of nodes with children sometimes points past
+// their last child, which counts for nothing
+pub fn day8(rng: &mut Rng) -> String {
+    fn node(rng: &mut Rng, depth: usize, out: &mut Vec<i64>) {
+        let children = if depth < 4 { rng.between(0, 3) } else { 0 };
+        let metadata = rng.between(1, 3);
+        out.push(children);
+        out.push(metadata);
+        for _ in 0..children {
+            node(rng, depth + 1, out);
+        }
+        for _ in 0..metadata {
+            out.push(rng.between(1, 9));
+        }
+    }
+
+    let mut numbers = Vec::new();
+    node(rng, 0, &mut numbers);
+    let numbers: Vec<_> = numbers.iter().map(|n| n.to_string()).collect();
+    numbers.join(" ") + "\n"
+}
+
+// Always at least one scoring marble
+pub fn day9(rng: &mut Rng) -> String {
+    format!(
+        "{} players; last marble is worth {} points\n",
+        rng.between(1, 30),
+        rng.between(23, 3000)
+    )
+}
+
+// Lights that spell out a random message after some number of seconds,
+// returned along with the input. Pairs of lights at opposite corners of
+// the message move apart, so the lights are never closer together than
+// when they show the message
+pub fn day10(rng: &mut Rng) -> (String, i64) {
+    let seconds = rng.between(3, 50);
+    let (left, top) = (rng.between(-30, 30), rng.between(-30, 30));
+    let (right, bottom) = (left + rng.between(1, 12), top + rng.between(1, 8));
+    let mut lights = vec![
+        ((left, top), (1, 1)),
+        ((left, top), (-1, -1)),
+        ((right, bottom), (1, 1)),
+        ((right, bottom), (-1, -1)),
+    ];
+    for _ in 0..rng.between(0, 20) {
+        let p = (rng.between(left, right), rng.between(top, bottom));
+        lights.push((p, (rng.between(-1, 1), rng.between(-1, 1))));
+    }
+    rng.shuffle(&mut lights);
+
+    let input = lines(lights.into_iter().map(|((x, y), (vx, vy))| {
+        format!(
+            "position=<{:>3}, {:>3}> velocity=<{:>2}, {:>2}>",
+            x - vx * seconds,
+            y - vy * seconds,
+            vx,
+            vy
+        )
+    }));
+    (input, seconds)
+}
+
+// A grid serial number
+pub fn day11(rng: &mut Rng) -> String {
+    format!("{}\n", rng.between(1, 9999))
+}
+
+// A row of pots and some rules. Empty pots never grow a plant, otherwise
+// plants would appear endlessly in both directions
+pub fn day12(rng: &mut Rng) -> String {
+    let pot = |plant: bool| if plant { '#' } else { '.' };
+    let state: String = (0..rng.between(1, 30))
+        .map(|_| pot(rng.chance(0.5)))
+        .collect();
+    let mut input = format!("initial state: {}\n\n", state);
+    for pattern in 0..32 {
+        if !rng.chance(0.7) {
+            continue;
+        }
+        let pots: String = (0..5).map(|i| pot(pattern & (1 << i) != 0)).collect();
+        let grows = pattern != 0 && rng.chance(0.5);
+        input.push_str(&format!("{} => {}\n", pots, pot(grows)));
+    }
+
+    input
+}
+
+// Separate rectangular loops of track with a pair of carts going opposite
+// ways on each, so every cart eventually crashes. One loop has a third
+// cart which is the last one left
+pub fn day13(rng: &mut Rng) -> String {
+    let loops = rng.between(1, 3) as usize;
+    let sizes: Vec<(usize, usize)> = (0..loops)
+        .map(|_| (rng.between(3, 8) as usize, rng.between(3, 6) as usize))
+        .collect();
+    let width = sizes.iter().map(|s| s.0 + 1).sum();
+    let height = sizes.iter().map(|s| s.1).max().unwrap();
+    let mut tracks = Grid::new(width, height, ' ');
+
+    let mut left = 0;
+    let extra = rng.below(loops);
+    for (i, &(w, h)) in sizes.iter().enumerate() {
+        let top = rng.below(height - h + 1);
+        let (right, bottom) = (left + w - 1, top + h - 1);
+        for x in left..=right {
+            tracks[Point::new(x, top)] = '-';
+            tracks[Point::new(x, bottom)] = '-';
+        }
+        for y in top..=bottom {
+            tracks[Point::new(left, y)] = '|';
+            tracks[Point::new(right, y)] = '|';
+        }
+        tracks[Point::new(left, top)] = '/';
+        tracks[Point::new(right, bottom)] = '/';
+        tracks[Point::new(right, top)] = '\\';
+        tracks[Point::new(left, bottom)] = '\\';
+
+        // Straight pieces only, carts don't start on corners
+        let mut edges = Vec::new();
+        for x in left + 1..right {
+            edges.push((Point::new(x, top), '>', '<'));
+            edges.push((Point::new(x, bottom), '<', '>'));
+        }
+        for y in top + 1..bottom {
+            edges.push((Point::new(right, y), 'v', '^'));
+            edges.push((Point::new(left, y), '^', 'v'));
+        }
+        rng.shuffle(&mut edges);
+        let carts = if i == extra { 3 } else { 2 };
+        for (n, &(p, clockwise, anticlockwise)) in edges.iter().take(carts).enumerate() {
+            tracks[p] = match n {
+                0 => clockwise,
+                1 => anticlockwise,
+                _ => *rng.pick(&[clockwise, anticlockwise]),
+            };
+        }
+
+        left += w + 1;
+    }
+
+    lines(
+        tracks
+            .rows()
+            .map(|row| row.iter().collect::<String>().trim_end().to_owned()),
+    )
+}
+
+// A number of recipes which, read as digits, is somewhere near the start
+// of the scoreboard. Not every number is, nothing ever scores `00`
+pub fn day14(rng: &mut Rng) -> String {
+    let mut recipes = vec![3, 7];
+    let (mut a, mut b) = (0, 1);
+    while recipes.len() < 2000 {
+        let sum = recipes[a] + recipes[b];
+        if sum >= 10 {
+            recipes.push(sum / 10);
+        }
+        recipes.push(sum % 10);
+        a = (a + 1 + recipes[a]) % recipes.len();
+        b = (b + 1 + recipes[b]) % recipes.len();
+    }
+
+    loop {
+        let (start, len) = (rng.below(recipes.len() - 6), rng.between(1, 6) as usize);
+        if recipes[start] != 0 {
+            let digits: Vec<_> = recipes[start..start + len]
+                .iter()
+                .map(|r| r.to_string())
+                .collect();
+            return digits.concat() + "\n";
+        }
+    }
+}
+
+// A walled cave where every open tile is reachable, so that every battle
+// ends, with a few elves and goblins
+pub fn day15(rng: &mut Rng) -> String {
+    loop {
+        let (width, height) = (rng.between(5, 10) as usize, rng.between(5, 9) as usize);
+        let mut cave = Grid::from_fn(width, height, |p| {
+            p.x == 0 || p.y == 0 || p.x == width - 1 || p.y == height - 1
+        })
+        .map(|&wall| if wall { '#' } else { '.' });
+        for p in cave.points() {
+            if cave[p] == '.' && rng.chance(0.2) {
+                cave[p] = '#';
+            }
+        }
+
+        // Wall off everything but the largest open area
+        let mut largest: Vec<Point> = Vec::new();
+        let mut seen = HashSet::new();
+        for start in cave.points() {
+            if cave[start] == '#' || !seen.insert(start) {
+                continue;
+            }
+            let mut area = vec![start];
+            let mut i = 0;
+            while i < area.len() {
+                for n in cave.neighbours4(area[i]) {
+                    if cave[n] == '.' && seen.insert(n) {
+                        area.push(n);
+                    }
+                }
+                i += 1;
+            }
+            if area.len() > largest.len() {
+                largest = area;
+            }
+        }
+        for p in cave.points() {
+            if !largest.contains(&p) {
+                cave[p] = '#';
+            }
+        }
+
+        let (elves, goblins) = (rng.between(1, 3) as usize, rng.between(1, 3) as usize);
+        if largest.len() < elves + goblins {
+            continue;
+        }
+        rng.shuffle(&mut largest);
+        for (i, &p) in largest.iter().take(elves + goblins).enumerate() {
+            cave[p] = if i < elves { 'E' } else { 'G' };
+        }
+
+        return cave.to_string();
+    }
+}
+
+// Samples of a random opcode numbering, with enough of them that the
+// numbering can be worked out, then a test program. Returned along with
+// register 0 after running the program
+pub fn day16(rng: &mut Rng) -> (String, usize) {
+    let mut numbering: Vec<usize> = (0..16).collect();
+    rng.shuffle(&mut numbering);
+
+    let mut input = String::new();
+    // Bit sets of the opcodes each number could be, 0 until it's sampled
+    let mut candidates = vec![0u32; 16];
+    while !resolves(&candidates) {
+        let number = rng.below(16);
+        let [a, b, c] = [rng.below(4), rng.below(4), rng.below(4)];
+        let before = [rng.below(4), rng.below(4), rng.below(4), rng.below(4)];
+        let after = execute(numbering[number], a, b, c, before);
+
+        let mut behaves_like = 0;
+        for op in 0..16 {
+            if execute(op, a, b, c, before) == after {
+                behaves_like |= 1 << op;
+            }
+        }
+        candidates[number] = match candidates[number] {
+            0 => behaves_like,
+            c => c & behaves_like,
+        };
+
+        input.push_str(&format!(
+            "Before: {:?}\n{} {} {} {}\nAfter:  {:?}\n\n",
+            before, number, a, b, c, after
+        ));
+    }
+
+    input.push('\n');
+    let mut registers = [0; 4];
+    for _ in 0..rng.between(1, 30) {
+        let (number, a, b, c) = (rng.below(16), rng.below(4), rng.below(4), rng.below(4));
+        registers = execute(numbering[number], a, b, c, registers);
+        input.push_str(&format!("{} {} {} {}\n", number, a, b, c));
+    }
+
+    (input, registers[0])
+}
+
+// Whether repeatedly fixing opcodes with a single candidate fixes them all
+fn resolves(candidates: &[u32]) -> bool {
+    let mut candidates = candidates.to_vec();
+    let mut fixed = 0u32;
+    loop {
+        let single = candidates
+            .iter()
+            .find(|c| c.count_ones() == 1 && *c & fixed == 0)
+            .cloned();
+        match single {
+            Some(op) => {
+                fixed |= op;
+                for c in candidates.iter_mut().filter(|c| c.count_ones() > 1) {
+                    *c &= !op;
+                }
+            }
+            None => return fixed.count_ones() == 16,
+        }
+    }
+}
+
+// The opcodes in the puzzle's order, addr through eqrr
+pub fn execute(op: usize, a: usize, b: usize, c: usize, mut r: [usize; 4]) -> [usize; 4] {
+    r[c] = match op {
+        0 => r[a] + r[b],
+        1 => r[a] + b,
+        2 => r[a] * r[b],
+        3 => r[a] * b,
+        4 => r[a] & r[b],
+        5 => r[a] & b,
+        6 => r[a] | r[b],
+        7 => r[a] | b,
+        8 => r[a],
+        9 => a,
+        10 => (a > r[b]) as usize,
+        11 => (r[a] > b) as usize,
+        12 => (r[a] > r[b]) as usize,
+        13 => (a == r[b]) as usize,
+        14 => (r[a] == b) as usize,
+        _ => (r[a] == r[b]) as usize,
+    };
+    r
+}
+
+// Clay veins around the spring, some of them shaped into buckets
+pub fn day17(rng: &mut Rng) -> String {
+    let mut veins = Vec::new();
+    for _ in 0..rng.between(1, 8) {
+        let (x, y) = (rng.between(485, 515), rng.between(1, 30));
+        let len = rng.between(0, 8);
+        if rng.chance(0.4) {
+            let depth = rng.between(1, 6);
+            veins.push(format!("y={}, x={}..{}", y + depth, x, x + len));
+            veins.push(format!("x={}, y={}..{}", x, y, y + depth));
+            veins.push(format!("x={}, y={}..{}", x + len, y, y + depth));
+        } else if rng.chance(0.5) {
+            veins.push(format!("x={}, y={}..{}", x, y, y + len));
+        } else {
+            veins.push(format!("y={}, x={}..{}", y, x, x + len));
+        }
+    }
+
+    lines(veins.into_iter())
+}
+
+// A random input for `day`, if there's a generator for it
+pub fn input(day: u8, rng: &mut Rng) -> Option<String> {
+    let input = match day {
+        1 => day1(rng),
+        2 => day2(rng),
+        3 => day3(rng),
+        4 => day4(rng),
+        5 => day5(rng),
+        6 => day6(rng),
+        7 => day7(rng),
+        8 => day8(rng),
+        9 => day9(rng),
+        10 => day10(rng).0,
+        11 => day11(rng),
+        12 => day12(rng),
+        13 => day13(rng),
+        14 => day14(rng),
+        15 => day15(rng),
+        16 => day16(rng).0,
+        17 => day17(rng),
+        _ => return None,
+    };
+    Some(input)
+}
+
+fn lines<I: Iterator<Item = String>>(lines: I) -> String {
+    lines.map(|l| l + "\n").collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rng() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+        for _ in 0..100 {
+            let n = a.between(-3, 3);
+            assert_eq!(n, b.between(-3, 3));
+            assert!((-3..=3).contains(&n));
+        }
+
+        let mut items: Vec<_> = (0..10).collect();
+        a.shuffle(&mut items);
+        items.sort_unstable();
+        assert_eq!(items, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_inputs_parse() {
+        use crate::runner;
+
+        check(20, |rng| {
+            for solution in runner::SOLUTIONS {
+                let input = input(solution.day, rng).unwrap();
+                if let Err(e) = solution.solve(&input, Some(1)) {
+                    panic!("{}\n{}", e, input);
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,274 @@
+use crate::error::{self, ParseError};
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+use std::ops::{Index, IndexMut};
+
+// A position on a grid, ordered in "reading order" (top to bottom, then left to right)
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub struct Point {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Point {
+    pub fn new(x: usize, y: usize) -> Self {
+        Point { x, y }
+    }
+}
+
+impl PartialOrd for Point {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Point {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.y, self.x).cmp(&(other.y, other.x))
+    }
+}
+
+impl From<(usize, usize)> for Point {
+    fn from(t: (usize, usize)) -> Self {
+        Point { x: t.0, y: t.1 }
+    }
+}
+
+// A dense, row-major grid of cells
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+
+    // Parses a character map, one row per line. Rows shorter than the longest
+    // are padded with `fill`, `cell` returns `None` for characters it doesn't know
+    pub fn parse<F>(day: u8, input: &str, fill: T, mut cell: F) -> Result<Self, ParseError>
+    where
+        F: FnMut(char) -> Option<T>,
+    {
+        let lines: Vec<_> = error::lines(day, input).collect();
+        let width = lines
+            .iter()
+            .map(|l| l.text().chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut cells = Vec::with_capacity(width * lines.len());
+        for line in &lines {
+            let mut len = 0;
+            for (i, c) in line.text().char_indices() {
+                cells.push(cell(c).ok_or_else(|| line.unexpected_char(i, c))?);
+                len += 1;
+            }
+            cells.extend((len..width).map(|_| fill.clone()));
+        }
+
+        Ok(Grid {
+            width,
+            height: lines.len(),
+            cells,
+        })
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn from_fn<F>(width: usize, height: usize, f: F) -> Self
+    where
+        F: FnMut(Point) -> T,
+    {
+        Grid {
+            width,
+            height,
+            cells: points(width, height).map(f).collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn in_bounds(&self, p: Point) -> bool {
+        p.x < self.width && p.y < self.height
+    }
+
+    pub fn get(&self, p: Point) -> Option<&T> {
+        if self.in_bounds(p) {
+            Some(&self.cells[p.y * self.width + p.x])
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, p: Point) -> Option<&mut T> {
+        if self.in_bounds(p) {
+            Some(&mut self.cells[p.y * self.width + p.x])
+        } else {
+            None
+        }
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        // `chunks` panics on 0, an empty grid has no rows anyway
+        self.cells.chunks(self.width.max(1))
+    }
+
+    // Every position in reading order. Doesn't borrow the grid so
+    // cells can be updated while walking it
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        points(self.width, self.height)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> {
+        self.points().zip(self.cells.iter())
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    // Up, left, right and down neighbours that are on the grid, in reading order
+    pub fn neighbours4(&self, p: Point) -> impl Iterator<Item = Point> {
+        let offsets = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+        self.offsets(p, offsets.to_vec())
+    }
+
+    // All eight surrounding neighbours that are on the grid, in reading order
+    pub fn neighbours8(&self, p: Point) -> impl Iterator<Item = Point> {
+        let offsets = [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ];
+        self.offsets(p, offsets.to_vec())
+    }
+
+    fn offsets(&self, p: Point, offsets: Vec<(isize, isize)>) -> impl Iterator<Item = Point> {
+        let (width, height) = (self.width, self.height);
+        offsets.into_iter().filter_map(move |(dx, dy)| {
+            let x = p.x.checked_add_signed(dx)?;
+            let y = p.y.checked_add_signed(dy)?;
+            if x < width && y < height {
+                Some(Point { x, y })
+            } else {
+                None
+            }
+        })
+    }
+}
+
+fn points(width: usize, height: usize) -> impl Iterator<Item = Point> {
+    (0..height).flat_map(move |y| (0..width).map(move |x| Point { x, y }))
+}
+
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, p: Point) -> &T {
+        assert!(self.in_bounds(p), "{:?} is outside the grid", p);
+        &self.cells[p.y * self.width + p.x]
+    }
+}
+
+impl<T> IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, p: Point) -> &mut T {
+        assert!(self.in_bounds(p), "{:?} is outside the grid", p);
+        &mut self.cells[p.y * self.width + p.x]
+    }
+}
+
+// Renders one line per row, like the puzzle maps
+impl<T: Display> Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                write!(f, "{}", cell)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAP: &str = "#..\n.#\n";
+
+    #[test]
+    fn test_parse_and_display() {
+        let grid = Grid::parse(0, MAP, ' ', Some).unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 2));
+        assert_eq!(grid[Point::new(1, 1)], '#');
+        assert_eq!(grid[Point::new(2, 1)], ' ');
+        assert_eq!(grid.to_string(), "#..\n.# \n");
+
+        let err = Grid::parse(0, MAP, false, |c| match c {
+            '.' => Some(false),
+            _ => None,
+        })
+        .unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+    }
+
+    #[test]
+    fn test_reading_order() {
+        let grid = Grid::from_fn(3, 2, |p| p);
+        let points: Vec<_> = grid.iter().map(|(p, cell)| (p, *cell)).collect();
+        assert!(points.iter().all(|(p, cell)| p == cell));
+        assert!(points.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(grid.row(1), &[(0, 1).into(), (1, 1).into(), (2, 1).into()]);
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let grid = Grid::new(3, 3, 0);
+        let corner: Vec<_> = grid.neighbours4(Point::new(0, 0)).collect();
+        assert_eq!(corner, vec![Point::new(1, 0), Point::new(0, 1)]);
+
+        let centre: Vec<_> = grid.neighbours4(Point::new(1, 1)).collect();
+        assert_eq!(
+            centre,
+            vec![(1, 0).into(), (0, 1).into(), (2, 1).into(), (1, 2).into()]
+        );
+        assert_eq!(grid.neighbours8(Point::new(1, 1)).count(), 8);
+        assert_eq!(grid.neighbours8(Point::new(2, 2)).count(), 3);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut grid = Grid::new(2, 2, 0);
+        assert!(grid.get(Point::new(2, 0)).is_none());
+        *grid.get_mut(Point::new(1, 1)).unwrap() = 5;
+        assert_eq!(grid[Point::new(1, 1)], 5);
+        assert!(!grid.in_bounds(Point::new(0, 2)));
+    }
+}
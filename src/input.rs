@@ -0,0 +1,150 @@
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    io::{self, BufRead},
+    iter,
+};
+
+const TAB_WIDTH: usize = 8;
+
+// Puts puzzle input into the form every generator expects, whatever
+// editor or OS it was saved with: no byte order mark, `\n` line endings,
+// tabs expanded to spaces, no trailing whitespace on a line and a single
+// newline at the end. Leading whitespace and blank lines between sections
+// are kept, some days depend on them
+pub fn normalize(input: &str) -> Cow<'_, str> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    if is_normalized(input) {
+        return Cow::Borrowed(input);
+    }
+
+    let input = input.replace("\r\n", "\n").replace('\r', "\n");
+    let mut lines: Vec<String> = input.lines().map(expand_tabs).collect();
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+
+    let mut normalized = lines.join("\n");
+    if !normalized.is_empty() {
+        normalized.push('\n');
+    }
+    Cow::Owned(normalized)
+}
+
+// The lines `normalize` would give, read one at a time so that a large
+// input never has to be held in memory. Blank lines are held back until
+// a line follows them, so that none are left at the end
+pub fn lines<R: BufRead>(reader: R) -> Lines<R> {
+    Lines {
+        reader,
+        first: true,
+        blank: 0,
+        ready: VecDeque::new(),
+    }
+}
+
+pub struct Lines<R> {
+    reader: R,
+    first: bool,
+    blank: usize,
+    ready: VecDeque<String>,
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.ready.is_empty() {
+            let mut raw = String::new();
+            match self.reader.read_line(&mut raw) {
+                Ok(0) => return None,
+                Ok(_) => (),
+                Err(e) => return Some(Err(e)),
+            }
+
+            let mut line = raw.as_str();
+            if self.first {
+                line = line.strip_prefix('\u{feff}').unwrap_or(line);
+                self.first = false;
+            }
+            let line = line.strip_suffix('\n').unwrap_or(line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            // Anything left is a lone `\r` ending a line
+            for line in line.split('\r').map(expand_tabs) {
+                if line.is_empty() {
+                    self.blank += 1;
+                } else {
+                    self.ready.extend(iter::repeat_n(String::new(), self.blank));
+                    self.ready.push_back(line);
+                    self.blank = 0;
+                }
+            }
+        }
+
+        self.ready.pop_front().map(Ok)
+    }
+}
+
+// Also drops trailing whitespace, which tabs can be part of
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    for c in line.trim_end().chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - expanded.chars().count() % TAB_WIDTH;
+            expanded.extend(iter::repeat_n(' ', spaces));
+        } else {
+            expanded.push(c);
+        }
+    }
+    expanded
+}
+
+fn is_normalized(input: &str) -> bool {
+    !input.contains(['\r', '\t'])
+        && input.len() > 1
+        && input.ends_with('\n')
+        && !input.ends_with("\n\n")
+        && input.lines().all(|l| l.len() == l.trim_end().len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("\u{feff}1\r\n2\r\n"), "1\n2\n");
+        assert_eq!(normalize("a  \n\nb\t\n\n\n"), "a\n\nb\n");
+        assert_eq!(normalize("1\r2"), "1\n2\n");
+        assert_eq!(normalize("\tx\ty"), "        x       y\n");
+        assert_eq!(normalize("  /->-\\\r\n\r\n\r\n"), "  /->-\\\n");
+        assert_eq!(normalize(""), "");
+        assert_eq!(normalize("\r\n \n"), "");
+        assert_eq!(normalize("\n"), "");
+        assert!(matches!(normalize("a\n\nb\n"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_lines() {
+        let inputs = [
+            "\u{feff}1\r\n2\r\n",
+            "a  \n\nb\t\n\n\n",
+            "1\r2\r\n\r3",
+            "\tx\ty",
+            "\n\n  /->-\\\r\n\r\n\r\n",
+            "",
+            "\r\n \n",
+        ];
+        for input in &inputs {
+            let lines: Vec<String> = lines(input.as_bytes()).map(Result::unwrap).collect();
+            let normalized = normalize(input);
+            let expected: Vec<&str> = normalized.lines().collect();
+            assert_eq!(lines, expected, "{:?}", input);
+        }
+
+        let invalid: &[u8] = b"1\n\xff\n";
+        let mut lines = lines(invalid);
+        assert_eq!(lines.next().unwrap().unwrap(), "1");
+        assert!(lines.next().unwrap().is_err());
+    }
+}
@@ -6,6 +6,7 @@ extern crate aoc_runner_derive;
 #[macro_use]
 extern crate lazy_static;
 
+pub mod answer;
 pub mod day1;
 pub mod day10;
 pub mod day11;
@@ -23,5 +24,12 @@ pub mod day6;
 pub mod day7;
 pub mod day8;
 pub mod day9;
+pub mod error;
+#[cfg(test)]
+pub mod generators;
+pub mod grid;
+pub mod input;
+pub mod render;
+pub mod runner;
 
 aoc_lib! { year = 2018 }
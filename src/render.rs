@@ -0,0 +1,407 @@
+// Pictures of the simulation days' states, written as PPM or PNG so they
+// can be looked at without any other tools. Frames of an animation are
+// numbered files in a directory
+use crate::error::ParseError;
+use crate::grid::Grid;
+use std::{
+    error::Error,
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub const BLACK: Rgb = Rgb(0, 0, 0);
+    pub const WHITE: Rgb = Rgb(255, 255, 255);
+
+    // Scales each channel by `f` out of 255
+    pub fn fade(self, f: u8) -> Rgb {
+        let scale = |c: u8| (u16::from(c) * u16::from(f) / 255) as u8;
+        Rgb(scale(self.0), scale(self.1), scale(self.2))
+    }
+}
+
+// A colour for the `i`th of some things. Stepping round the hue circle by
+// the golden ratio keeps neighbouring indices apart however many there are
+pub fn distinct(i: usize) -> Rgb {
+    let hue = (i as f64 * 0.618_034).fract() * 6.0;
+    let x = (255.0 * (1.0 - (hue % 2.0 - 1.0).abs())) as u8;
+    match hue as u8 {
+        0 => Rgb(255, x, 0),
+        1 => Rgb(x, 255, 0),
+        2 => Rgb(0, 255, x),
+        3 => Rgb(0, x, 255),
+        4 => Rgb(x, 0, 255),
+        _ => Rgb(255, 0, x),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+}
+
+impl Image {
+    // One pixel per cell, coloured by `palette`
+    pub fn from_grid<T, F>(grid: &Grid<T>, palette: F) -> Self
+    where
+        F: Fn(&T) -> Rgb,
+    {
+        Image {
+            width: grid.width(),
+            height: grid.height(),
+            pixels: grid.iter().map(|(_, cell)| palette(cell)).collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Rgb {
+        self.pixels[y * self.width + x]
+    }
+
+    // Every pixel becomes a `factor` by `factor` square
+    pub fn scale(&self, factor: usize) -> Image {
+        let factor = factor.max(1);
+        let (width, height) = (self.width * factor, self.height * factor);
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| self.pixel(x / factor, y / factor))
+            .collect();
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn rgb_bytes(&self, row: usize) -> impl Iterator<Item = u8> + '_ {
+        self.pixels[row * self.width..(row + 1) * self.width]
+            .iter()
+            .flat_map(|p| vec![p.0, p.1, p.2])
+    }
+
+    pub fn write_ppm<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for row in 0..self.height {
+            w.write_all(&self.rgb_bytes(row).collect::<Vec<_>>())?;
+        }
+        Ok(())
+    }
+
+    // 8-bit RGB, stored without compression so no deflate implementation is
+    // needed. Fine for pictures this size
+    pub fn write_png<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // Bit depth, colour type (RGB), compression, filter and interlace methods
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        // Each row starts with its filter type, 0 for none
+        let mut raw = Vec::with_capacity(self.height * (self.width * 3 + 1));
+        for row in 0..self.height {
+            raw.push(0);
+            raw.extend(self.rgb_bytes(row));
+        }
+
+        w.write_all(b"\x89PNG\r\n\x1a\n")?;
+        write_chunk(&mut w, b"IHDR", &header)?;
+        write_chunk(&mut w, b"IDAT", &zlib_stored(&raw))?;
+        write_chunk(&mut w, b"IEND", &[])
+    }
+
+    // The format is picked from the extension, `.ppm` or `.png`
+    pub fn save(&self, path: &Path) -> Result<(), RenderError> {
+        let format = Format::from_path(path)?;
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        match format {
+            Format::Ppm => self.write_ppm(&mut file)?,
+            Format::Png => self.write_png(&mut file)?,
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    w.write_all(&crc.to_be_bytes())
+}
+
+// A zlib stream of uncompressed deflate blocks, each up to 65535 bytes
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        out.push(last as u8);
+        out.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32<'a, I: Iterator<Item = &'a u8>>(bytes: I) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= u32::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ppm,
+    Png,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Ppm => "ppm",
+            Format::Png => "png",
+        }
+    }
+
+    fn from_path(path: &Path) -> Result<Format, RenderError> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ppm") => Ok(Format::Ppm),
+            Some("png") => Ok(Format::Png),
+            _ => Err(RenderError::Format(path.to_owned())),
+        }
+    }
+}
+
+// Where a day's pictures go. Frames are numbered in the order they're
+// pushed, a still is just a single frame
+pub struct Frames {
+    output: Output,
+    scale: usize,
+    // Only every `every`th frame is kept, long simulations have thousands
+    every: usize,
+    pushed: usize,
+}
+
+enum Output {
+    Dir {
+        dir: PathBuf,
+        format: Format,
+        written: Vec<PathBuf>,
+    },
+    Memory(Vec<Image>),
+}
+
+impl Frames {
+    // Writes `frame_00000.<ext>`, `frame_00001.<ext>`, ... into `dir`,
+    // creating it if needed
+    pub fn to_dir(dir: &Path, format: Format) -> Result<Self, RenderError> {
+        fs::create_dir_all(dir)?;
+        Ok(Frames::new(Output::Dir {
+            dir: dir.to_owned(),
+            format,
+            written: Vec::new(),
+        }))
+    }
+
+    pub fn in_memory() -> Self {
+        Frames::new(Output::Memory(Vec::new()))
+    }
+
+    fn new(output: Output) -> Self {
+        Frames {
+            output,
+            scale: 1,
+            every: 1,
+            pushed: 0,
+        }
+    }
+
+    pub fn scale(mut self, factor: usize) -> Self {
+        self.scale = factor.max(1);
+        self
+    }
+
+    pub fn every(mut self, n: usize) -> Self {
+        self.every = n.max(1);
+        self
+    }
+
+    pub fn push(&mut self, image: Image) -> Result<(), RenderError> {
+        self.pushed += 1;
+        if !(self.pushed - 1).is_multiple_of(self.every) {
+            return Ok(());
+        }
+
+        let image = image.scale(self.scale);
+        match &mut self.output {
+            Output::Dir {
+                dir,
+                format,
+                written,
+            } => {
+                let path = dir.join(format!("frame_{:05}.{}", written.len(), format.extension()));
+                image.save(&path)?;
+                written.push(path);
+            }
+            Output::Memory(images) => images.push(image),
+        }
+        Ok(())
+    }
+
+    // The files written so far
+    pub fn written(&self) -> &[PathBuf] {
+        match &self.output {
+            Output::Dir { written, .. } => written,
+            Output::Memory(_) => &[],
+        }
+    }
+
+    // The frames kept so far when rendering in memory
+    pub fn images(&self) -> &[Image] {
+        match &self.output {
+            Output::Memory(images) => images,
+            Output::Dir { .. } => &[],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RenderError {
+    Parse(ParseError),
+    Io(io::Error),
+    // The path doesn't end in `.ppm` or `.png`
+    Format(PathBuf),
+    // There's nothing to draw for the day
+    NoPicture(u8),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::Parse(e) => write!(f, "{}", e),
+            RenderError::Io(e) => write!(f, "{}", e),
+            RenderError::Format(path) => {
+                write!(f, "{} should end in .ppm or .png", path.display())
+            }
+            RenderError::NoPicture(day) => write!(f, "day {} has nothing to draw", day),
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+impl From<ParseError> for RenderError {
+    fn from(e: ParseError) -> Self {
+        RenderError::Parse(e)
+    }
+}
+
+impl From<io::Error> for RenderError {
+    fn from(e: io::Error) -> Self {
+        RenderError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn checkerboard() -> Image {
+        let grid = Grid::from_fn(3, 2, |p| (p.x + p.y) % 2 == 0);
+        Image::from_grid(
+            &grid,
+            |&white| if white { Rgb::WHITE } else { Rgb(255, 0, 0) },
+        )
+    }
+
+    #[test]
+    fn test_ppm() {
+        let mut ppm = Vec::new();
+        checkerboard().write_ppm(&mut ppm).unwrap();
+        let (header, pixels) = ppm.split_at(11);
+        assert_eq!(header, b"P6\n3 2\n255\n");
+        assert_eq!(&pixels[..6], &[255, 255, 255, 255, 0, 0]);
+        assert_eq!(pixels.len(), 3 * 2 * 3);
+    }
+
+    #[test]
+    fn test_png() {
+        let image = checkerboard().scale(2);
+        assert_eq!((image.width(), image.height()), (6, 4));
+        assert_eq!(image.pixel(2, 1), Rgb(255, 0, 0));
+
+        let mut png = Vec::new();
+        image.write_png(&mut png).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(&png[png.len() - 8..], b"IEND\xae\x42\x60\x82");
+
+        // Reads the pixels back out of the single stored block
+        let idat = &png[8 + 25..];
+        assert_eq!(&idat[4..8], b"IDAT");
+        let zlib = &idat[8..];
+        let len = u16::from_le_bytes([zlib[3], zlib[4]]) as usize;
+        assert_eq!((zlib[2], len), (1, 4 * (6 * 3 + 1)));
+        let raw = &zlib[7..7 + len];
+        assert_eq!(&raw[..4], &[0, 255, 255, 255]);
+        assert_eq!(&raw[1 + 6..1 + 9], &[255, 0, 0]);
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND".iter()), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_frames() {
+        let mut frames = Frames::in_memory().every(2).scale(3);
+        for _ in 0..5 {
+            frames.push(checkerboard()).unwrap();
+        }
+        assert_eq!(frames.images().len(), 3);
+        assert_eq!(frames.images()[0].width(), 9);
+    }
+
+    #[test]
+    fn test_bad_format() {
+        let err = checkerboard().save(Path::new("picture.gif")).unwrap_err();
+        assert_eq!(err.to_string(), "picture.gif should end in .ppm or .png");
+    }
+}
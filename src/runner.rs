@@ -0,0 +1,642 @@
+use crate::answer::Answer;
+use crate::error::{ParseError, SolveError};
+use crate::input;
+use crate::render::{Frames, RenderError};
+use crate::{
+    day1, day10, day11, day12, day13, day14, day15, day16, day17, day2, day3, day4, day5, day6,
+    day7, day8, day9,
+};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::time::{Duration, Instant};
+
+// A day's generator and solvers wired up to take the raw puzzle input, so
+// that callers don't need to know about each day's types. The input is
+// normalized before any day sees it. Bad input comes back as the
+// generator's `ParseError`, input with no answer as a `SolveError`
+pub struct Solution {
+    pub day: u8,
+    pub parts: u8,
+    run: fn(&str, &mut Run) -> Result<(), RunError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunError {
+    Parse(ParseError),
+    Solve(SolveError),
+}
+
+impl Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunError::Parse(e) => e.fmt(f),
+            RunError::Solve(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for RunError {}
+
+impl From<ParseError> for RunError {
+    fn from(e: ParseError) -> Self {
+        RunError::Parse(e)
+    }
+}
+
+impl From<SolveError> for RunError {
+    fn from(e: SolveError) -> Self {
+        RunError::Solve(e)
+    }
+}
+
+impl Solution {
+    pub fn has_part(&self, part: u8) -> bool {
+        (1..=self.parts).contains(&part)
+    }
+
+    // Answers `part`, or every part when it's `None`
+    pub fn solve(&self, input: &str, part: Option<u8>) -> Result<Vec<(u8, Answer)>, RunError> {
+        let mut run = Run::new(self.day, part, 1);
+        (self.run)(&input::normalize(input), &mut run)?;
+        Ok(run.answers)
+    }
+
+    // Times the generator and `part` (or every part), running each step `repeats` times
+    pub fn time(
+        &self,
+        input: &str,
+        part: Option<u8>,
+        repeats: u32,
+    ) -> Result<Vec<Timing>, RunError> {
+        let mut run = Run::new(self.day, part, repeats.max(1));
+        (self.run)(&input::normalize(input), &mut run)?;
+        Ok(run.timings)
+    }
+}
+
+// Handed to a day's `run` to step through it, only solving the parts that
+// were asked for and timing each step as it goes
+struct Run {
+    day: u8,
+    part: Option<u8>,
+    repeats: u32,
+    answers: Vec<(u8, Answer)>,
+    timings: Vec<Timing>,
+}
+
+impl Run {
+    fn new(day: u8, part: Option<u8>, repeats: u32) -> Self {
+        Run {
+            day,
+            part,
+            repeats,
+            answers: Vec::new(),
+            timings: Vec::new(),
+        }
+    }
+
+    fn generator<I, F>(&mut self, generate: F) -> Result<I, ParseError>
+    where
+        F: Fn() -> Result<I, ParseError>,
+    {
+        let mut samples = Vec::new();
+        let mut input = None;
+        for _ in 0..self.repeats {
+            let start = Instant::now();
+            let generated = generate()?;
+            samples.push(start.elapsed());
+            input = Some(generated);
+        }
+        self.timings.push(Timing {
+            step: Step::Generator,
+            samples,
+        });
+
+        Ok(input.unwrap())
+    }
+
+    fn part<A, F>(&mut self, part: u8, solve: F)
+    where
+        A: Into<Answer>,
+        F: Fn() -> A,
+    {
+        if let Some(answer) = self.timed(part, solve) {
+            self.answers.push((part, answer.into()));
+        }
+    }
+
+    // For parts that can find there's no answer
+    fn try_part<A, E, F>(&mut self, part: u8, solve: F) -> Result<(), SolveError>
+    where
+        A: Into<Answer>,
+        E: Display,
+        F: Fn() -> Result<A, E>,
+    {
+        match self.timed(part, solve) {
+            Some(Ok(answer)) => self.answers.push((part, answer.into())),
+            Some(Err(e)) => {
+                return Err(SolveError {
+                    day: self.day,
+                    part,
+                    reason: e.to_string(),
+                })
+            }
+            None => (),
+        }
+        Ok(())
+    }
+
+    // What `solve` gave on its last run, or `None` if `part` wasn't asked for
+    fn timed<T, F: Fn() -> T>(&mut self, part: u8, solve: F) -> Option<T> {
+        if self.part.is_some_and(|p| p != part) {
+            return None;
+        }
+
+        let mut samples = Vec::new();
+        let mut solved = None;
+        for _ in 0..self.repeats {
+            let start = Instant::now();
+            solved = Some(solve());
+            samples.push(start.elapsed());
+        }
+        self.timings.push(Timing {
+            step: Step::Part(part),
+            samples,
+        });
+        solved
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Generator,
+    Part(u8),
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Generator => write!(f, "generator"),
+            Step::Part(p) => write!(f, "part {}", p),
+        }
+    }
+}
+
+// How long each repeat of a step took
+#[derive(Debug, Clone)]
+pub struct Timing {
+    pub step: Step,
+    pub samples: Vec<Duration>,
+}
+
+impl Timing {
+    pub fn min(&self) -> Duration {
+        self.samples.iter().min().cloned().unwrap_or_default()
+    }
+
+    pub fn median(&self) -> Duration {
+        let mut sorted = self.samples.clone();
+        sorted.sort();
+        match sorted.len() {
+            0 => Duration::default(),
+            n if n % 2 == 0 => (sorted[n / 2 - 1] + sorted[n / 2]) / 2,
+            n => sorted[n / 2],
+        }
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::default();
+        }
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+}
+
+pub const SOLUTIONS: &[Solution] = &[
+    Solution {
+        day: 1,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day1::input_gen(input))?;
+            run.part(1, || day1::solve_part1(&input));
+            run.try_part(2, || {
+                day1::solve_part2(&input).ok_or("no frequency is ever reached twice")
+            })?;
+            Ok(())
+        },
+    },
+    Solution {
+        day: 2,
+        parts: 2,
+        run: |input, run| {
+            run.try_part(1, || {
+                day2::solve_part1(input)
+                    .and_then(|sum| i64::try_from(sum).ok())
+                    .ok_or("the checksum is too big to hold")
+            })?;
+            run.try_part(2, || day2::solve_part2(input))?;
+            Ok(())
+        },
+    },
+    Solution {
+        day: 3,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day3::input_gen(input))?;
+            run.part(1, || day3::solve_part1(&input));
+            run.try_part(2, || {
+                day3::solve_part2(&input).ok_or("every claim overlaps another")
+            })?;
+            Ok(())
+        },
+    },
+    Solution {
+        day: 4,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day4::input_gen(input))?;
+            run.try_part(1, || day4::solve_part1(&input))?;
+            run.try_part(2, || day4::solve_part2(&input))?;
+            Ok(())
+        },
+    },
+    Solution {
+        day: 5,
+        parts: 2,
+        run: |input, run| {
+            run.part(1, || day5::solve_part1(input));
+            run.part(2, || day5::solve_part2(input));
+            Ok(())
+        },
+    },
+    Solution {
+        day: 6,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day6::input_gen(input))?;
+            run.part(1, || day6::solve_part1(&input));
+            run.part(2, || day6::solve_part2(&input));
+            Ok(())
+        },
+    },
+    Solution {
+        day: 7,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day7::input_gen(input))?;
+            run.part(1, || day7::solve_part1(&input));
+            run.part(2, || day7::solve_part2(&input));
+            Ok(())
+        },
+    },
+    Solution {
+        day: 8,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day8::input_gen(input))?;
+            run.try_part(1, || day8::solve_part1(&input))?;
+            run.try_part(2, || day8::solve_part2(&input))?;
+            Ok(())
+        },
+    },
+    Solution {
+        day: 9,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day9::input_gen(input))?;
+            run.part(1, || day9::solve_part1(&input));
+            run.part(2, || day9::solve_part2(&input));
+            Ok(())
+        },
+    },
+    Solution {
+        day: 10,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day10::input_gen(input))?;
+            run.part(1, || day10::solve_part1(&input));
+            run.part(2, || day10::solve_part2(&input));
+            Ok(())
+        },
+    },
+    Solution {
+        day: 11,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day11::input_gen(input))?;
+            run.part(1, || day11::solve_part1(&input));
+            run.part(2, || day11::solve_part2(&input));
+            Ok(())
+        },
+    },
+    Solution {
+        day: 12,
+        parts: 1,
+        run: |input, run| {
+            let input = run.generator(|| day12::input_gen(input))?;
+            run.part(1, || day12::solve_part1(&input));
+            Ok(())
+        },
+    },
+    Solution {
+        day: 13,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day13::input_gen(input))?;
+            run.try_part(1, || day13::solve_part1(&input))?;
+            run.try_part(2, || day13::solve_part2(&input))?;
+            Ok(())
+        },
+    },
+    Solution {
+        day: 14,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day14::input_gen(input))?;
+            run.part(1, || day14::solve_part1(&input));
+            run.part(2, || day14::solve_part2(&input));
+            Ok(())
+        },
+    },
+    Solution {
+        day: 15,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day15::input_gen(input))?;
+            run.part(1, || day15::solve_part1(&input));
+            run.part(2, || day15::solve_part2(&input));
+            Ok(())
+        },
+    },
+    Solution {
+        day: 16,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day16::input_gen(input))?;
+            run.part(1, || day16::solve_part1(&input));
+            run.try_part(2, || {
+                day16::solve_part2(&input).ok_or("the samples don't pin down every opcode")
+            })?;
+            Ok(())
+        },
+    },
+    Solution {
+        day: 17,
+        parts: 2,
+        run: |input, run| {
+            let input = run.generator(|| day17::input_gen(input))?;
+            run.part(1, || day17::solve_part1(&input));
+            run.part(2, || day17::solve_part2(&input));
+            Ok(())
+        },
+    },
+];
+
+pub fn find(day: u8) -> Option<&'static Solution> {
+    SOLUTIONS.iter().find(|s| s.day == day)
+}
+
+// Draws the days that have something to look at into `frames`, a still
+// or one frame per step of the simulation
+pub fn render(day: u8, input: &str, frames: &mut Frames) -> Result<(), RenderError> {
+    let input = input::normalize(input);
+    match day {
+        6 => day6::render(&day6::input_gen(&input)?, frames),
+        10 => day10::render(&day10::input_gen(&input)?, frames),
+        13 => day13::render(&day13::input_gen(&input)?, frames),
+        15 => day15::render(&day15::input_gen(&input)?, frames),
+        17 => day17::render(&day17::input_gen(&input)?, frames),
+        _ => Err(RenderError::NoPicture(day)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::generators::{self, Rng};
+
+    #[test]
+    fn test_registry() {
+        for (i, s) in SOLUTIONS.iter().enumerate() {
+            assert_eq!(s.day as usize, i + 1);
+        }
+        assert!(!find(12).unwrap().has_part(2));
+        assert!(find(18).is_none());
+    }
+
+    #[test]
+    fn test_run_day() {
+        let input = "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2";
+        let day8 = find(8).unwrap();
+        assert_eq!(
+            day8.solve(input, Some(2)).unwrap(),
+            vec![(2, Answer::Int(66))]
+        );
+        assert_eq!(day8.solve(input, None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_bad_input() {
+        let err = find(8)
+            .unwrap()
+            .solve("2 3 0 3 10 11\n12 1 one 0 1 99", Some(1))
+            .unwrap_err();
+        match err {
+            RunError::Parse(err) => assert_eq!((err.day, err.line, err.column), (8, 2, 6)),
+            _ => panic!("expected a parse error, got {}", err),
+        }
+
+        let err = find(8).unwrap().solve("1 1 0 1", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "day 8, line 1, column 1: missing the rest of the tree"
+        );
+    }
+
+    // Days 9 and 11 used to answer for the committed input whatever they were given
+    #[test]
+    fn test_every_day_reads_its_input() {
+        let day9 = find(9).unwrap();
+        let answers = day9
+            .solve("10 players; last marble is worth 1618 points", Some(1))
+            .unwrap();
+        assert_eq!(answers[0].1.to_string(), "8317");
+        assert!(matches!(day9.solve("", None), Err(RunError::Parse(_))));
+
+        let day11 = find(11).unwrap();
+        assert_eq!(
+            day11.solve("18\n", Some(1)).unwrap()[0].1.to_string(),
+            "33,45"
+        );
+        assert!(matches!(day11.solve("", None), Err(RunError::Parse(_))));
+    }
+
+    #[test]
+    fn test_no_answer() {
+        let day1 = find(1).unwrap();
+        assert_eq!(day1.solve("+1\n", Some(1)).unwrap().len(), 1);
+        let err = day1.solve("+1\n", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "day 1, part 2: no frequency is ever reached twice"
+        );
+
+        let err = find(3)
+            .unwrap()
+            .solve("#1 @ 1,1: 2x2\n#2 @ 2,2: 2x2\n", None)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "day 3, part 2: every claim overlaps another"
+        );
+
+        let err = find(4)
+            .unwrap()
+            .solve("[1518-11-01 00:05] wakes up\n", None)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "day 4, part 1: the guard log doesn't add up: line 1 [1518-11-01 00:05]: nobody is on duty yet"
+        );
+    }
+
+    // These used to crash, hang or give a nonsense answer
+    #[test]
+    fn test_empty_input() {
+        for &day in &[6, 7, 10, 13, 15, 16] {
+            let err = find(day).unwrap().solve("\n", None).unwrap_err();
+            assert!(matches!(err, RunError::Parse(_)), "day {}: {}", day, err);
+        }
+
+        let err = find(6).unwrap().solve("1, 1\n", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "day 6, line 2, column 1: missing at least two coordinates"
+        );
+        let err = find(13).unwrap().solve("->-<-\n", None).unwrap_err();
+        assert_eq!(err.to_string(), "day 13, part 2: every cart crashes");
+    }
+
+    #[test]
+    fn test_time() {
+        let timings = find(8)
+            .unwrap()
+            .time("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2", Some(1), 3)
+            .unwrap();
+        let steps: Vec<_> = timings.iter().map(|t| t.step).collect();
+        assert_eq!(steps, vec![Step::Generator, Step::Part(1)]);
+        assert!(timings.iter().all(|t| t.samples.len() == 3));
+    }
+
+    #[test]
+    fn test_timing_stats() {
+        let ms = Duration::from_millis;
+        let timing = Timing {
+            step: Step::Part(1),
+            samples: vec![ms(4), ms(1), ms(10), ms(2)],
+        };
+        assert_eq!(timing.min(), ms(1));
+        assert_eq!(timing.median(), ms(3));
+        assert_eq!(timing.mean(), Duration::from_micros(4250));
+    }
+
+    #[test]
+    fn test_render() {
+        let input = include_str!("../input/tests/d13-2.txt");
+        let mut frames = Frames::in_memory();
+        render(13, input, &mut frames).unwrap();
+        let images = frames.images();
+        // Three ticks until one cart is left, plus the last state
+        assert_eq!(images.len(), 4);
+        assert_eq!((images[0].width(), images[0].height()), (7, 7));
+
+        let mut frames = Frames::in_memory();
+        let input = include_str!("../input/tests/d15.txt");
+        render(15, input, &mut frames).unwrap();
+        assert_eq!(frames.images().len(), 48);
+
+        let err = render(2, "abcdef", &mut frames).unwrap_err();
+        assert!(matches!(err, RenderError::NoPicture(2)));
+        let err = render(6, "1, x", &mut frames).unwrap_err();
+        assert!(matches!(err, RenderError::Parse(_)));
+    }
+
+    // As if it had been saved by an editor on Windows, with a byte order
+    // mark, CRLF line endings and stray whitespace
+    fn windows_copy(input: &str, rng: &mut Rng) -> String {
+        let mut copy = "\u{feff}".to_owned();
+        for line in input.lines() {
+            copy.push_str(line);
+            if rng.chance(0.3) {
+                let padding = *rng.pick(&[" ", "\t", " \t "]);
+                copy.push_str(padding);
+            }
+            copy.push_str("\r\n");
+        }
+        copy + "\r\n\r\n"
+    }
+
+    // Every day is handed the same text for both. Part 1 is enough to
+    // run each day's parsing
+    #[test]
+    fn test_windows_inputs() {
+        generators::check(10, |rng| {
+            for solution in SOLUTIONS {
+                let input = generators::input(solution.day, rng).unwrap();
+                let copy = windows_copy(&input, rng);
+                assert_eq!(input::normalize(&copy), input::normalize(&input));
+                if let Err(e) = solution.solve(&copy, Some(1)) {
+                    panic!("{}\n{}", e, copy);
+                }
+            }
+        });
+    }
+
+    // The same, calling each day's generator directly like cargo-aoc does,
+    // without the runner normalizing the input first
+    #[test]
+    fn test_windows_inputs_in_generators() {
+        macro_rules! check_generators {
+            ($rng:expr, $($day:ident: $n:expr,)*) => {
+                $(
+                    let input = generators::input($n, $rng).unwrap();
+                    let copy = windows_copy(&input, $rng);
+                    assert_eq!(
+                        format!("{:?}", $day::input_gen(&copy)),
+                        format!("{:?}", $day::input_gen(&input)),
+                        "day {}",
+                        $n
+                    );
+                    assert!($day::input_gen(&copy).is_ok(), "day {}\n{}", $n, copy);
+                )*
+            };
+        }
+        generators::check(10, |rng| {
+            check_generators!(rng,
+                day1: 1, day3: 3, day4: 4, day6: 6, day8: 8, day9: 9, day10: 10,
+                day11: 11, day12: 12, day13: 13, day14: 14,
+            );
+
+            // These don't print, or not in a fixed order, so what part 1
+            // makes of them is compared
+            for &day in &[7, 15, 16, 17] {
+                let input = generators::input(day, rng).unwrap();
+                let copy = windows_copy(&input, rng);
+                let part1 = |input: &str| match day {
+                    7 => day7::input_gen(input).map(|graph| day7::solve_part1(&graph)),
+                    15 => day15::input_gen(input).map(|cave| day15::solve_part1(&cave).to_string()),
+                    16 => day16::input_gen(input).map(|m| day16::solve_part1(&m).to_string()),
+                    _ => day17::input_gen(input).map(|r| day17::solve_part1(&r).to_string()),
+                };
+                assert!(part1(&copy).is_ok(), "day {}\n{}", day, copy);
+                assert_eq!(part1(&copy), part1(&input), "day {}", day);
+            }
+
+            let input = generators::input(2, rng).unwrap();
+            let copy = windows_copy(&input, rng);
+            assert_eq!(day2::solve_part1(&copy), day2::solve_part1(&input));
+            assert_eq!(day2::solve_part2(&copy), day2::solve_part2(&input));
+        });
+    }
+}
@@ -0,0 +1,154 @@
+// Golden answers for the real puzzle inputs. Every part of every day is run
+// against input/2018/day<N>.txt and checked against input/2018/answers.txt,
+// so a refactor that changes an answer fails here
+use advent_of_code_2018::{input, runner};
+use std::fs;
+
+const ANSWERS: &str = include_str!("../input/2018/answers.txt");
+
+#[derive(Debug, PartialEq)]
+struct Expected {
+    day: u8,
+    part: u8,
+    answer: String,
+}
+
+// Lines look like `Day  1 - Part 2: 413`. Pictures start with an empty
+// `Day 10 - Part 1:` and have their rows on the following, indented lines.
+// Blank lines and `#` comments are skipped
+fn expected() -> Vec<Expected> {
+    let mut expected: Vec<Expected> = Vec::new();
+    for (i, line) in ANSWERS.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match (line.strip_prefix("    "), expected.last_mut()) {
+            (Some(row), Some(e)) => {
+                if !e.answer.is_empty() {
+                    e.answer.push('\n');
+                }
+                e.answer.push_str(row);
+            }
+            _ => expected.push(
+                parse_line(line)
+                    .unwrap_or_else(|| panic!("answers.txt line {}: bad line `{}`", i + 1, line)),
+            ),
+        }
+    }
+
+    expected
+}
+
+fn parse_line(line: &str) -> Option<Expected> {
+    let rest = line.strip_prefix("Day")?;
+    let (day, rest) = rest.split_once(" - Part ")?;
+    let (part, answer) = rest.split_once(':')?;
+    Some(Expected {
+        day: day.trim().parse().ok()?,
+        part: part.parse().ok()?,
+        answer: answer.trim_start().to_owned(),
+    })
+}
+
+fn read_input(day: u8) -> String {
+    fs::read_to_string(format!("input/2018/day{}.txt", day)).unwrap()
+}
+
+fn check_day(day: u8) {
+    let input = read_input(day);
+    let solution = runner::find(day).unwrap();
+    let expected: Vec<_> = expected().into_iter().filter(|e| e.day == day).collect();
+    assert!(!expected.is_empty(), "no answers for day {}", day);
+
+    let answers = solution
+        .solve(&input, None)
+        .unwrap_or_else(|err| panic!("day {}: {}", day, err));
+    for e in expected {
+        let answer = answers
+            .iter()
+            .find(|(part, _)| *part == e.part)
+            .map(|(_, answer)| answer.to_string())
+            .unwrap_or_else(|| panic!("day {} has no part {}", day, e.part));
+        assert_eq!(answer, e.answer, "day {} part {}", day, e.part);
+    }
+}
+
+macro_rules! answers {
+    ($($name:ident: $day:expr,)*) => {
+        $(
+            #[test]
+            fn $name() {
+                check_day($day);
+            }
+        )*
+    };
+}
+
+answers! {
+    day1: 1,
+    day2: 2,
+    day3: 3,
+    day4: 4,
+    day5: 5,
+    day6: 6,
+    day7: 7,
+    day8: 8,
+    day9: 9,
+    day10: 10,
+    day11: 11,
+    day12: 12,
+    day13: 13,
+    day14: 14,
+    day15: 15,
+    day16: 16,
+    day17: 17,
+}
+
+// Every day is handed the same text for a copy of its input saved on
+// Windows, so the answers can't change
+#[test]
+fn crlf_inputs() {
+    for solution in runner::SOLUTIONS {
+        let input = read_input(solution.day);
+        let crlf = format!("\u{feff}{}", input.replace('\n', "\r\n"));
+        assert_eq!(
+            input::normalize(&crlf),
+            input::normalize(&input),
+            "day {}",
+            solution.day
+        );
+    }
+}
+
+#[test]
+fn every_part_has_an_answer() {
+    let expected = expected();
+    for solution in runner::SOLUTIONS {
+        for part in 1..=2 {
+            let has_answer = expected
+                .iter()
+                .any(|e| e.day == solution.day && e.part == part);
+            assert_eq!(
+                solution.has_part(part),
+                has_answer,
+                "day {} part {}",
+                solution.day,
+                part
+            );
+        }
+    }
+}
+
+#[test]
+fn test_parse_line() {
+    assert_eq!(
+        parse_line("Day 11 - Part 2: 237,227,14"),
+        Some(Expected {
+            day: 11,
+            part: 2,
+            answer: "237,227,14".to_owned(),
+        })
+    );
+    assert_eq!(parse_line("Day 10 - Part 1:").unwrap().answer, "");
+    assert_eq!(parse_line("Day 1 Part 1: 493"), None);
+}